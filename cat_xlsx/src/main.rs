@@ -1,23 +1,143 @@
-use calamine::{open_workbook_auto, Data, Reader};
-use clap::Parser;
+use calamine::{open_workbook_auto, Data, DataType, Reader, Sheets, Xlsx};
+use clap::{Parser, ValueEnum};
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
 use std::error::Error;
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{self, Cursor, IsTerminal, Read, Seek};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Path to the xlsx file
     xlsx_file: PathBuf,
+
+    /// Only print sheets whose name matches this glob (e.g. '2024-*')
+    #[arg(long, value_name = "PATTERN", value_parser = parse_glob)]
+    sheet_glob: Option<GlobMatcher>,
+
+    /// Print at most N sheets (after --sheet-glob filtering)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_sheets: Option<u64>,
+
+    /// First sheet row to print (1-based, as numbered in the spreadsheet)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    from_row: Option<u32>,
+
+    /// Last sheet row to print (1-based, inclusive)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    to_row: Option<u32>,
+
+    /// Columns to print, in order, as letters (A,C,F) or 0-based indices (0,2,5)
+    #[arg(long, value_delimiter = ',', value_parser = parse_column)]
+    columns: Vec<usize>,
+
+    /// Stream rows as they are read instead of loading each sheet into memory
+    #[arg(long)]
+    stream: bool,
+
+    /// Truncate each printed cell to at most N characters, marking cut cells with an ellipsis
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    max_col_width: Option<u64>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Tsv)]
+    format: Format,
+
+    /// Only report the number of rows, columns and non-empty cells per sheet
+    #[arg(long)]
+    count: bool,
+
+    /// Leave out sheets without any non-empty cell instead of printing them as `(empty)`
+    #[arg(long)]
+    skip_empty: bool,
+
+    /// Stack all sheets into one CSV with a leading `sheet` column, keeping only the
+    /// first sheet's header row (requires `--format csv`)
+    #[arg(long)]
+    merge_sheets: bool,
+
+    /// Prefix each cell with its underlying data type, e.g. `Float:3.14` or `Empty:`
+    #[arg(long)]
+    types: bool,
+
+    /// Backslash-escape tabs, newlines and backslashes in cells so each row stays on one line
+    /// (`--format tsv` only)
+    #[arg(long)]
+    tsv_escape: bool,
+
+    /// Text written for formula-error cells in CSV output; `{}` is replaced by the
+    /// specific error (e.g. `#DIV/0!`)
+    #[arg(long, value_name = "TEXT", default_value = "#ERROR")]
+    error_text: String,
+
+    /// Password for an encrypted workbook
+    #[arg(long)]
+    password: Option<String>,
+
+    /// Only print rows with a cell containing this text, highlighting matches on a terminal
+    #[arg(long, value_name = "TERM")]
+    search: Option<String>,
+
+    /// Treat the --search term as a regular expression
+    #[arg(long, requires = "search")]
+    regex: bool,
+
+    /// The compiled --search term
+    #[arg(skip)]
+    search_pattern: Option<Regex>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Tab-separated cells, one row per line
+    Tsv,
+    /// A GitHub-flavored Markdown table per sheet, using the first row as header
+    Markdown,
+    /// Comma-separated values with plain cell values and no sheet headings
+    Csv,
+}
+
+fn parse_glob(s: &str) -> Result<GlobMatcher, String> {
+    Glob::new(s)
+        .map(|glob| glob.compile_matcher())
+        .map_err(|e| e.to_string())
+}
+
+/// Parses a column given either as spreadsheet letters ("A", "AB") or as a
+/// 0-based numeric index ("0", "27").
+fn parse_column(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    if let Ok(index) = s.parse::<usize>() {
+        return Ok(index);
+    }
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(format!(
+            "invalid column '{}': expected letters or an index",
+            s
+        ));
+    }
+    let mut index = 0usize;
+    for c in s.chars() {
+        let digit = (c.to_ascii_uppercase() as u8 - b'A') as usize + 1;
+        index = index
+            .checked_mul(26)
+            .and_then(|i| i.checked_add(digit))
+            .ok_or_else(|| format!("column '{}' is out of range", s))?;
+    }
+    Ok(index - 1)
 }
 
 /// Reads and displays the contents of an Excel (.xlsx) file.
-/// Iterates through all worksheets and prints their contents in a tab-separated format.
+/// Iterates through all worksheets and prints their contents in a tab-separated format
+/// (or as Markdown tables with `--format markdown`).
 /// Each worksheet is clearly delimited and labeled.
 fn main() -> Result<(), Box<dyn Error>> {
     // Get the path to the xlsx file from command-line arguments
-    let args = Args::parse();
+    let mut args = Args::parse();
 
-    let path = args.xlsx_file;
+    let path = &args.xlsx_file;
     // Check if the file exists
     if !path.exists() {
         eprintln!("Error: File not found");
@@ -32,32 +152,540 @@ fn main() -> Result<(), Box<dyn Error>> {
         std::process::exit(1);
     }
 
+    if args.merge_sheets && args.format != Format::Csv {
+        eprintln!("Error: --merge-sheets requires --format csv");
+        std::process::exit(1);
+    }
+
+    if let (Some(from_row), Some(to_row)) = (args.from_row, args.to_row) {
+        if from_row > to_row {
+            eprintln!("Error: --from-row must not be after --to-row");
+            std::process::exit(1);
+        }
+    }
+
+    if args.tsv_escape && args.format != Format::Tsv {
+        eprintln!("Error: --tsv-escape requires --format tsv");
+        std::process::exit(1);
+    }
+
+    // A plain search term matches literally
+    if let Some(term) = &args.search {
+        let pattern = if args.regex {
+            term.clone()
+        } else {
+            regex::escape(term)
+        };
+        match Regex::new(&pattern) {
+            Ok(re) => args.search_pattern = Some(re),
+            Err(e) => {
+                eprintln!("Error: Invalid --search pattern: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Encrypted workbooks are wrapped in an OLE container and must be decrypted first
+    let is_encrypted = match is_ole_container(path) {
+        Ok(is_encrypted) => is_encrypted,
+        Err(e) => {
+            eprintln!("Error: Failed to read '{}': {}", path.display(), e);
+            std::process::exit(1);
+        }
+    };
+    if is_encrypted {
+        let Some(password) = &args.password else {
+            eprintln!("Error: Workbook is password-protected; pass it with --password");
+            std::process::exit(1);
+        };
+        let mut workbook = match open_encrypted_workbook(path, password) {
+            Ok(workbook) => workbook,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        return print_workbook(&mut workbook, &args);
+    }
+
     // Open the workbook (auto-detects the format)
     let mut workbook = open_workbook_auto(path)?;
+    print_workbook(&mut workbook, &args)
+}
+
+/// Checks whether the file starts with the OLE compound-file signature,
+/// which is how password-protected xlsx files are stored.
+fn is_ole_container(path: &Path) -> io::Result<bool> {
+    const OLE_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+    let mut header = [0u8; 8];
+    let mut file = File::open(path)?;
+    match file.read_exact(&mut header) {
+        Ok(()) => Ok(header == OLE_SIGNATURE),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Decrypts a password-protected workbook into memory and opens it.
+/// A wrong password yields bytes that are not a valid xlsx package, so both
+/// decryption and parse failures are reported as an incorrect password.
+fn open_encrypted_workbook(path: &Path, password: &str) -> Result<Sheets<Cursor<Vec<u8>>>, String> {
+    let decrypted = match office_crypto::decrypt_from_file(path, password) {
+        Ok(decrypted) => decrypted,
+        Err(office_crypto::DecryptError::IoError(e)) => {
+            return Err(format!("Failed to read '{}': {}", path.display(), e))
+        }
+        Err(office_crypto::DecryptError::InvalidHeader) => {
+            return Err(format!(
+                "'{}' is not a valid encrypted workbook",
+                path.display()
+            ))
+        }
+        Err(office_crypto::DecryptError::Unimplemented(what)) => {
+            return Err(format!("Unsupported workbook encryption: {}", what))
+        }
+        Err(_) => return Err("Incorrect password for encrypted workbook".to_string()),
+    };
+    Xlsx::new(Cursor::new(decrypted))
+        .map(Sheets::Xlsx)
+        .map_err(|_| "Incorrect password for encrypted workbook".to_string())
+}
 
-    // Iterate over the worksheets
-    let sheet_names = workbook.sheet_names().to_owned();
+/// Prints every worksheet of an open workbook according to `args`.
+fn print_workbook<RS: Read + Seek>(
+    workbook: &mut Sheets<RS>,
+    args: &Args,
+) -> Result<(), Box<dyn Error>> {
+    // Iterate over the worksheets. When merging, only the first non-empty
+    // sheet contributes its header row.
+    let sheet_names: Vec<String> = workbook
+        .sheet_names()
+        .into_iter()
+        .filter(|name| {
+            args.sheet_glob
+                .as_ref()
+                .is_none_or(|glob| glob.is_match(name))
+        })
+        .take(args.max_sheets.map_or(usize::MAX, |n| n as usize))
+        .collect();
+    let mut header_written = false;
     for sheet_name in sheet_names {
-        if let Ok(range) = workbook.worksheet_range(&sheet_name) {
-            println!("Sheet: {}", sheet_name);
-            for row in range.rows() {
-                for cell in row {
-                    match cell {
-                        Data::Empty => print!("(empty)\t"),
-                        Data::String(s) => print!("{}\t", s),
-                        Data::Float(f) => print!("{}\t", f),
-                        Data::Int(i) => print!("{}\t", i),
-                        Data::Bool(b) => print!("{}\t", b),
-                        Data::Error(e) => print!("Error({:?})\t", e),
-                        Data::DateTime(dt) => print!("DateTime({})\t", dt),
-                        _ => print!("(unknown)\t"),
+        let skip_header = args.merge_sheets && header_written;
+        if args.stream {
+            if let Sheets::Xlsx(xlsx) = workbook {
+                let result = if args.count {
+                    stream_count(xlsx, &sheet_name, args.skip_empty).map(|_| false)
+                } else {
+                    stream_sheet(xlsx, &sheet_name, args, skip_header)
+                };
+                match result {
+                    Ok(printed) => header_written |= printed,
+                    Err(e) => {
+                        eprintln!("Error: Failed to stream sheet '{}': {}", sheet_name, e)
                     }
                 }
+                continue;
+            }
+        }
+        if let Ok(range) = workbook.worksheet_range(&sheet_name) {
+            if args.count {
+                let (rows, columns) = range.get_size();
+                let non_empty = range.used_cells().filter(|(_, _, c)| !c.is_empty()).count();
+                if non_empty > 0 || !args.skip_empty {
+                    print_count(&sheet_name, rows, columns, non_empty);
+                }
+                continue;
+            }
+            if range.used_cells().all(|(_, _, c)| c.is_empty()) {
+                print_empty_sheet(&sheet_name, args);
+                continue;
+            }
+            let mut printer = SheetPrinter::new(&sheet_name, args, skip_header);
+            let start_row = range.start().map_or(0, |(row, _)| row);
+            for (row_number, row) in (start_row + 1..).zip(range.rows()) {
+                printer.print_row(row_number, row);
+            }
+            header_written |= printer.finish()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints one sheet in the selected output format, a row at a time.
+struct SheetPrinter<'a> {
+    args: &'a Args,
+    sheet_name: String,
+    rows_printed: usize,
+    first_row_seen: bool,
+    skip_header: bool,
+    header_pending: bool,
+    highlight: bool,
+    csv: Option<csv::Writer<io::Stdout>>,
+}
+
+impl<'a> SheetPrinter<'a> {
+    /// Prints the sheet heading. With `skip_header`, the sheet's first row is
+    /// dropped (used when merging sheets that share a header).
+    fn new(sheet_name: &str, args: &'a Args, skip_header: bool) -> Self {
+        match args.format {
+            Format::Tsv => println!("Sheet: {}", sheet_name),
+            Format::Markdown => println!("### {}\n", escape_markdown(sheet_name)),
+            Format::Csv => {}
+        }
+        SheetPrinter {
+            args,
+            sheet_name: sheet_name.to_string(),
+            rows_printed: 0,
+            first_row_seen: false,
+            skip_header,
+            header_pending: args.merge_sheets && !skip_header,
+            highlight: args.search_pattern.is_some()
+                && args.format != Format::Csv
+                && io::stdout().is_terminal(),
+            csv: (args.format == Format::Csv).then(|| {
+                csv::WriterBuilder::new()
+                    .flexible(true)
+                    .from_writer(io::stdout())
+            }),
+        }
+    }
+
+    /// Prints one row, projecting onto `--columns` when any are given.
+    /// Rows outside `--from-row`/`--to-row` are skipped, except for the header
+    /// row when merging sheets or printing Markdown. With `--search`, rows other
+    /// than the header without a matching cell are skipped too.
+    fn print_row(&mut self, row_number: u32, row: &[Data]) {
+        // Markdown tables take their header from the first row too
+        let is_header = (self.args.merge_sheets || self.args.format == Format::Markdown)
+            && !self.first_row_seen;
+        self.first_row_seen = true;
+        let in_window = self.args.from_row.is_none_or(|from| row_number >= from)
+            && self.args.to_row.is_none_or(|to| row_number <= to);
+        if !in_window && !is_header {
+            return;
+        }
+        if self.skip_header {
+            self.skip_header = false;
+            return;
+        }
+        let row: Vec<&Data> = if self.args.columns.is_empty() {
+            row.iter().collect()
+        } else {
+            self.args
+                .columns
+                .iter()
+                .map(|&col| row.get(col).unwrap_or(&Data::Empty))
+                .collect()
+        };
+
+        // Search the bare values so type wrappers and truncation don't affect matches
+        let matched: Vec<bool> = match &self.args.search_pattern {
+            Some(re) => row
+                .iter()
+                .map(|cell| re.is_match(&plain_value(cell)))
+                .collect(),
+            None => vec![false; row.len()],
+        };
+        if self.args.search_pattern.is_some() && !matched.contains(&true) && !is_header {
+            return;
+        }
+
+        let mut cells: Vec<String> = row
+            .iter()
+            .zip(&matched)
+            .map(|(cell, &matched)| {
+                let text = self.render_cell(cell);
+                if matched && self.highlight {
+                    format!("\x1b[1;31m{}\x1b[0m", text)
+                } else {
+                    text
+                }
+            })
+            .collect();
+
+        match self.args.format {
+            Format::Tsv => {
+                for cell in &cells {
+                    print!("{}\t", cell);
+                }
                 println!();
             }
-            println!("-----------------------------------");
+            Format::Markdown => {
+                println!("| {} |", cells.join(" | "));
+                // The first row is the header
+                if self.rows_printed == 0 {
+                    println!("|{}", " --- |".repeat(cells.len()));
+                }
+            }
+            Format::Csv => {
+                if self.args.merge_sheets {
+                    // The merged header names the column; data rows carry their sheet
+                    let first = if self.header_pending {
+                        self.header_pending = false;
+                        "sheet".to_string()
+                    } else {
+                        self.sheet_name.clone()
+                    };
+                    cells.insert(0, first);
+                }
+                if let Some(writer) = self.csv.as_mut() {
+                    // A failed write to stdout surfaces again on the final flush
+                    let _ = writer.write_record(&cells);
+                }
+            }
         }
+        self.rows_printed += 1;
     }
 
+    /// Prints the sheet footer and returns whether any row was printed.
+    fn finish(self) -> io::Result<bool> {
+        match self.args.format {
+            Format::Tsv => println!("-----------------------------------"),
+            Format::Markdown => println!(),
+            Format::Csv => {
+                if let Some(mut writer) = self.csv {
+                    writer.flush()?;
+                }
+            }
+        }
+        Ok(self.rows_printed > 0)
+    }
+
+    fn render_cell(&self, cell: &Data) -> String {
+        let text = if self.args.types {
+            plain_value(cell)
+        } else if self.args.format == Format::Csv {
+            // CSV carries the full, plain value so it can be loaded elsewhere,
+            // with errors replaced by a recognizable sentinel
+            return match cell {
+                Data::Error(e) => self.args.error_text.replace("{}", &e.to_string()),
+                _ => plain_value(cell),
+            };
+        } else {
+            match (self.args.format, cell) {
+                (Format::Markdown, Data::Empty) => String::new(),
+                _ => format_cell(cell),
+            }
+        };
+        let text = match self.args.max_col_width {
+            Some(max) if self.args.format != Format::Csv => truncate(&text, max as usize),
+            _ => text,
+        };
+        // The type prefix is kept whole so truncated cells still show their type
+        let text = if self.args.types {
+            format!("{}:{}", type_name(cell), text)
+        } else {
+            text
+        };
+        match self.args.format {
+            Format::Markdown => escape_markdown(&text),
+            Format::Tsv if self.args.tsv_escape => escape_tsv(&text),
+            Format::Tsv | Format::Csv => text,
+        }
+    }
+}
+
+/// Escapes text for a Markdown table cell: pipes are backslash-escaped and
+/// line breaks become `<br>` so a cell stays on one line.
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\n', '\r'], "<br>")
+}
+
+/// Escapes text for a TSV cell: backslashes, tabs and line breaks become
+/// `\\`, `\t`, `\n` and `\r`, so cells never split a row.
+fn escape_tsv(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Shortens `text` to at most `max` characters, ending with an ellipsis when cut.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Prints a sheet by reading its cells one at a time, so only the current row
+/// is held in memory. The layout matches `worksheet_range`: rows and columns
+/// start at the sheet's first used cell and gaps are printed as empty cells.
+/// Returns whether any row was printed.
+fn stream_sheet<RS: Read + Seek>(
+    xlsx: &mut Xlsx<RS>,
+    sheet_name: &str,
+    args: &Args,
+    skip_header: bool,
+) -> Result<bool, Box<dyn Error>> {
+    let mut reader = xlsx.worksheet_cells_reader(sheet_name)?;
+    let dimensions = reader.dimensions();
+    let (start_row, start_col) = dimensions.start;
+    let width = (dimensions.end.1 - start_col + 1) as usize;
+
+    // Read up to the first non-empty cell so a blank sheet can be reported as such
+    let mut leading = Vec::new();
+    loop {
+        let Some(cell) = reader.next_cell()? else {
+            print_empty_sheet(sheet_name, args);
+            return Ok(false);
+        };
+        let value: Data = cell.get_value().clone().into();
+        let found = !value.is_empty();
+        leading.push(Ok((cell.get_position(), value)));
+        if found {
+            break;
+        }
+    }
+    let rest = std::iter::from_fn(|| reader.next_cell().transpose())
+        .map(|cell| cell.map(|cell| (cell.get_position(), cell.get_value().clone().into())));
+
+    let mut printer = SheetPrinter::new(sheet_name, args, skip_header);
+    let mut row = vec![Data::Empty; width];
+    let mut current_row: Option<u32> = None;
+    for cell in leading.into_iter().chain(rest) {
+        let ((r, c), value): ((u32, u32), Data) = cell?;
+        if r < start_row || c < start_col {
+            continue;
+        }
+        if current_row != Some(r) {
+            if let Some(prev) = current_row {
+                printer.print_row(prev + 1, &row);
+                row.iter_mut().for_each(|cell| *cell = Data::Empty);
+                // Keep blank rows between used ones, as the range view does
+                for blank in prev + 1..r {
+                    printer.print_row(blank + 1, &row);
+                }
+            }
+            current_row = Some(r);
+        }
+        let c = (c - start_col) as usize;
+        if c >= row.len() {
+            row.resize(c + 1, Data::Empty);
+        }
+        row[c] = value;
+    }
+    if let Some(last) = current_row {
+        printer.print_row(last + 1, &row);
+    }
+
+    Ok(printer.finish()?)
+}
+
+/// Marks a sheet without any non-empty cell, unless `--skip-empty` drops it.
+fn print_empty_sheet(sheet_name: &str, args: &Args) {
+    if args.skip_empty {
+        return;
+    }
+    match args.format {
+        Format::Tsv => println!("Sheet: {} (empty)", sheet_name),
+        Format::Markdown => println!("### {} (empty)\n", escape_markdown(sheet_name)),
+        // CSV output has no per-sheet headings to mark
+        Format::Csv => {}
+    }
+}
+
+fn print_count(sheet_name: &str, rows: usize, columns: usize, non_empty: usize) {
+    println!(
+        "Sheet: {}\trows: {}\tcolumns: {}\tnon-empty cells: {}",
+        sheet_name, rows, columns, non_empty
+    );
+}
+
+/// Counts a sheet's cells while streaming it, without building the range.
+/// Rows and columns come from the sheet's recorded dimensions.
+fn stream_count<RS: Read + Seek>(
+    xlsx: &mut Xlsx<RS>,
+    sheet_name: &str,
+    skip_empty: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut reader = xlsx.worksheet_cells_reader(sheet_name)?;
+    let mut non_empty = 0;
+    while let Some(cell) = reader.next_cell()? {
+        if !cell.get_value().is_empty() {
+            non_empty += 1;
+        }
+    }
+    if non_empty == 0 && skip_empty {
+        return Ok(());
+    }
+    let dimensions = reader.dimensions();
+    let (rows, columns) = if non_empty == 0 {
+        (0, 0)
+    } else {
+        (
+            (dimensions.end.0 - dimensions.start.0 + 1) as usize,
+            (dimensions.end.1 - dimensions.start.1 + 1) as usize,
+        )
+    };
+    print_count(sheet_name, rows, columns, non_empty);
+
     Ok(())
 }
+
+/// Names the variant a cell was read as, for `--types`.
+fn type_name(cell: &Data) -> &'static str {
+    match cell {
+        Data::Empty => "Empty",
+        Data::String(_) => "String",
+        Data::Float(_) => "Float",
+        Data::Int(_) => "Int",
+        Data::Bool(_) => "Bool",
+        Data::Error(_) => "Error",
+        Data::DateTime(dt) if dt.is_duration() => "Duration",
+        Data::DateTime(_) => "DateTime",
+        Data::DateTimeIso(_) => "DateTimeIso",
+        Data::DurationIso(_) => "DurationIso",
+    }
+}
+
+/// Renders a cell as its bare value, without the type wrappers used for display.
+fn plain_value(cell: &Data) -> String {
+    match cell {
+        Data::Empty => String::new(),
+        Data::Error(e) => e.to_string(),
+        Data::DateTime(dt) if dt.is_duration() => dt
+            .as_duration()
+            .map_or_else(|| dt.to_string(), |d| d.to_string()),
+        Data::DateTime(dt) => dt
+            .as_datetime()
+            .map_or_else(|| dt.to_string(), |d| d.to_string()),
+        Data::DateTimeIso(s) | Data::DurationIso(s) => s.to_string(),
+        _ => format_cell(cell),
+    }
+}
+
+/// Renders a single cell value for display, keeping date/time and duration
+/// cells distinguishable from plain numbers.
+fn format_cell(cell: &Data) -> String {
+    match cell {
+        Data::Empty => "(empty)".to_string(),
+        Data::String(s) => s.to_string(),
+        Data::Float(f) => f.to_string(),
+        Data::Int(i) => i.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::Error(e) => format!("Error({:?})", e),
+        Data::DateTime(dt) if dt.is_duration() => match dt.as_duration() {
+            Some(d) => format!("Duration({})", d),
+            None => format!("Duration({})", dt),
+        },
+        Data::DateTime(dt) => match dt.as_datetime() {
+            Some(d) => format!("DateTime({})", d),
+            None => format!("DateTime({})", dt),
+        },
+        Data::DateTimeIso(s) => format!("DateTimeIso({})", s),
+        Data::DurationIso(s) => format!("DurationIso({})", s),
+    }
+}
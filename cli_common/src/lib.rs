@@ -0,0 +1,310 @@
+//! Helpers shared by the tools in this workspace.
+
+pub mod logging;
+pub mod progress;
+#[cfg(feature = "s3")]
+pub mod s3;
+
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::time::{Duration, SystemTime};
+
+const BINARY_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+const DECIMAL_UNITS: [&str; 7] = ["B", "KB", "MB", "GB", "TB", "PB", "EB"];
+
+/// Formats a byte count as a human-readable size.
+/// Uses powers of 1024 (KiB, MiB, ...) when `binary` is true and powers of
+/// 1000 (KB, MB, ...) otherwise. Plain byte counts are printed without decimals.
+pub fn format_size(bytes: u64, binary: bool) -> String {
+    let (base, units) = if binary {
+        (1024.0, &BINARY_UNITS)
+    } else {
+        (1000.0, &DECIMAL_UNITS)
+    };
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= base && unit < units.len() - 1 {
+        size /= base;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, units[0])
+    } else {
+        format!("{:.2} {}", size, units[unit])
+    }
+}
+
+/// Asks a yes/no question on stdin and returns true only for "y" or "yes".
+/// Returns true without prompting when `assume_yes` is set, and false when
+/// stdin is not a terminal or reaches EOF, so destructive actions never run
+/// unattended by accident.
+pub fn confirm(prompt: &str, assume_yes: bool) -> bool {
+    let stdin = io::stdin();
+    let is_terminal = stdin.is_terminal();
+    confirm_from(prompt, assume_yes, is_terminal, &mut stdin.lock())
+}
+
+/// Does the work of `confirm`, reading the answer from `input`.
+fn confirm_from(
+    prompt: &str,
+    assume_yes: bool,
+    is_terminal: bool,
+    input: &mut impl BufRead,
+) -> bool {
+    if assume_yes {
+        return true;
+    }
+    if !is_terminal {
+        eprintln!("{} (y/n): no terminal attached, assuming no", prompt);
+        return false;
+    }
+
+    print!("{} (y/n): ", prompt);
+    if io::stdout().flush().is_err() {
+        return false;
+    }
+
+    let mut answer = String::new();
+    match input.read_line(&mut answer) {
+        Ok(0) | Err(_) => false,
+        Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+    }
+}
+
+/// Parses a human-readable size such as `2GiB`, `500 MB`, `1.5G` or `1024`.
+/// `KiB`/`MiB`/... and bare `K`/`M`/... are powers of 1024, `KB`/`MB`/... are
+/// powers of 1000, and a plain number (optionally with `B`) is a byte count.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().map_err(|_| {
+        format!(
+            "invalid size '{}': expected a number with an optional unit",
+            s
+        )
+    })?;
+
+    let unit = unit.trim().to_ascii_uppercase();
+    let multiplier: u64 = match unit.as_str() {
+        "" | "B" => 1,
+        "K" | "KIB" => 1 << 10,
+        "M" | "MIB" => 1 << 20,
+        "G" | "GIB" => 1 << 30,
+        "T" | "TIB" => 1 << 40,
+        "P" | "PIB" => 1 << 50,
+        "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        "PB" => 1_000_000_000_000_000,
+        _ => return Err(format!("invalid size '{}': unknown unit '{}'", s, unit)),
+    };
+
+    let bytes = number * multiplier as f64;
+    if bytes > u64::MAX as f64 {
+        return Err(format!("invalid size '{}': too large", s));
+    }
+    Ok(bytes.round() as u64)
+}
+
+/// Parses a point in time given as a relative age such as `30m`, `12h`, `7d`
+/// or `2w` (counted back from now), or as a local date (`2024-05-01`) or
+/// date and time (`2024-05-01 13:30:00` or `2024-05-01T13:30:00`).
+pub fn parse_time(s: &str) -> Result<SystemTime, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    if !number.is_empty() {
+        let seconds_per_unit: Option<u64> = match unit {
+            "s" => Some(1),
+            "m" => Some(60),
+            "h" => Some(60 * 60),
+            "d" => Some(24 * 60 * 60),
+            "w" => Some(7 * 24 * 60 * 60),
+            _ => None,
+        };
+        if let Some(seconds_per_unit) = seconds_per_unit {
+            let age = number
+                .parse::<u64>()
+                .ok()
+                .and_then(|n| n.checked_mul(seconds_per_unit))
+                .ok_or_else(|| format!("invalid time '{}': too large", s))?;
+            return SystemTime::now()
+                .checked_sub(Duration::from_secs(age))
+                .ok_or_else(|| format!("invalid time '{}': too far in the past", s));
+        }
+    }
+
+    let naive = NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).expect("midnight is valid"))
+        })
+        .map_err(|_| {
+            format!(
+                "invalid time '{}': expected an age like 7d or a date like 2024-05-01",
+                s
+            )
+        })?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(SystemTime::from)
+        .ok_or_else(|| {
+            format!(
+                "invalid time '{}': does not exist in the local time zone",
+                s
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_size_prints_plain_bytes_without_decimals() {
+        assert_eq!(format_size(0, true), "0 B");
+        assert_eq!(format_size(0, false), "0 B");
+        assert_eq!(format_size(1023, true), "1023 B");
+        assert_eq!(format_size(999, false), "999 B");
+    }
+
+    #[test]
+    fn format_size_switches_unit_at_the_base() {
+        assert_eq!(format_size(1024, true), "1.00 KiB");
+        assert_eq!(format_size(1000, false), "1.00 KB");
+        assert_eq!(format_size(1000, true), "1000 B");
+        assert_eq!(format_size(1024, false), "1.02 KB");
+    }
+
+    #[test]
+    fn format_size_handles_multi_terabyte_sizes() {
+        assert_eq!(format_size(5 << 40, true), "5.00 TiB");
+        assert_eq!(format_size(5_000_000_000_000, false), "5.00 TB");
+        assert_eq!(format_size(5 << 40, false), "5.50 TB");
+    }
+
+    #[test]
+    fn confirm_assumes_yes_without_reading_input() {
+        assert!(confirm_from("Delete?", true, false, &mut io::empty()));
+        assert!(confirm_from("Delete?", true, true, &mut "no\n".as_bytes()));
+    }
+
+    #[test]
+    fn confirm_refuses_without_a_terminal() {
+        assert!(!confirm_from(
+            "Delete?",
+            false,
+            false,
+            &mut "yes\n".as_bytes()
+        ));
+    }
+
+    #[test]
+    fn confirm_refuses_on_eof() {
+        assert!(!confirm_from("Delete?", false, true, &mut io::empty()));
+    }
+
+    #[test]
+    fn confirm_accepts_only_y_or_yes() {
+        assert!(confirm_from(
+            "Delete?",
+            false,
+            true,
+            &mut " Yes\n".as_bytes()
+        ));
+        assert!(confirm_from("Delete?", false, true, &mut "y\n".as_bytes()));
+        assert!(!confirm_from(
+            "Delete?",
+            false,
+            true,
+            &mut "yep\n".as_bytes()
+        ));
+    }
+
+    #[test]
+    fn parse_size_reads_binary_and_decimal_units() {
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert_eq!(parse_size("500 MB"), Ok(500_000_000));
+        assert_eq!(parse_size("1.5G"), Ok(3 << 29));
+        assert_eq!(parse_size("2gib"), Ok(2 << 30));
+    }
+
+    #[test]
+    fn parse_size_reads_plain_byte_counts() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("1024B"), Ok(1024));
+    }
+
+    #[test]
+    fn parse_size_rejects_unknown_units_and_overflow() {
+        assert!(parse_size("5 parsecs")
+            .unwrap_err()
+            .contains("unknown unit"));
+        assert!(parse_size("GiB").is_err());
+        assert!(parse_size("20000000PiB").unwrap_err().contains("too large"));
+    }
+
+    /// Asserts that `time` is `age` before now, allowing for the time the test takes.
+    fn assert_age(time: SystemTime, age: Duration) {
+        let elapsed = SystemTime::now().duration_since(time).unwrap();
+        assert!(elapsed >= age && elapsed - age < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn parse_time_reads_relative_ages() {
+        assert_age(
+            parse_time("7d").unwrap(),
+            Duration::from_secs(7 * 24 * 60 * 60),
+        );
+        assert_age(
+            parse_time("2w").unwrap(),
+            Duration::from_secs(14 * 24 * 60 * 60),
+        );
+        assert_age(parse_time("30m").unwrap(), Duration::from_secs(30 * 60));
+    }
+
+    #[test]
+    fn parse_time_rejects_overflowing_ages() {
+        assert!(parse_time("99999999999999999999d")
+            .unwrap_err()
+            .contains("too large"));
+        assert!(parse_time("18446744073709551615w")
+            .unwrap_err()
+            .contains("too large"));
+    }
+
+    #[test]
+    fn parse_time_falls_back_to_dates_starting_with_digits() {
+        let local = |y, m, d, h, min| {
+            let naive = NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(h, min, 0)
+                .unwrap();
+            SystemTime::from(Local.from_local_datetime(&naive).earliest().unwrap())
+        };
+        assert_eq!(parse_time("2024-05-01"), Ok(local(2024, 5, 1, 0, 0)));
+        assert_eq!(
+            parse_time("2024-05-01T13:30:00"),
+            Ok(local(2024, 5, 1, 13, 30))
+        );
+        assert_eq!(
+            parse_time("2024-05-01 13:30:00"),
+            Ok(local(2024, 5, 1, 13, 30))
+        );
+    }
+
+    #[test]
+    fn parse_time_rejects_unknown_input() {
+        assert!(parse_time("7y").is_err());
+        assert!(parse_time("2024-13-01").is_err());
+        assert!(parse_time("yesterday").is_err());
+    }
+}
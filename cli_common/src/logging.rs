@@ -0,0 +1,23 @@
+//! File logging shared by the workspace tools.
+
+use chrono::Local;
+use env_logger::{Builder, Env, Target};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Sends `log` records to a timestamped file `<tool_name>_<YYYYmmdd_HHMMSS>.log`
+/// inside `log_dir`, creating the directory if needed. The level defaults to
+/// `info` and can be overridden with `RUST_LOG`. Returns the log file path.
+pub fn init(tool_name: &str, log_dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(log_dir)?;
+    let timestamp = Local::now().format("%Y%m%d_%H%M%S");
+    let log_path = log_dir.join(format!("{}_{}.log", tool_name, timestamp));
+    let file = File::create(&log_path)?;
+
+    Builder::from_env(Env::default().default_filter_or("info"))
+        .target(Target::Pipe(Box::new(file)))
+        .init();
+
+    Ok(log_path)
+}
@@ -0,0 +1,32 @@
+//! Consistently styled progress bars for the workspace tools.
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+const BAR_CHARS: &str = "##-";
+
+/// A bar counting discrete items such as files or lines.
+/// Use `set_prefix` for a short label of the phase and `set_message` for details.
+pub fn count_bar(total: u64) -> ProgressBar {
+    let pb = ProgressBar::new(total);
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{spinner:.green} {prefix} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}",
+        )
+        .unwrap()
+        .progress_chars(BAR_CHARS),
+    );
+    pb
+}
+
+/// A bar tracking a number of bytes, showing throughput alongside the totals.
+pub fn bytes_bar(total: u64) -> ProgressBar {
+    let pb = ProgressBar::new(total);
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{spinner:.green} {prefix} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta}) {msg}",
+        )
+        .unwrap()
+        .progress_chars(BAR_CHARS),
+    );
+    pb
+}
@@ -0,0 +1,60 @@
+//! S3 helpers shared by the tools that read from buckets.
+
+use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::operation::list_objects_v2::ListObjectsV2Error;
+use aws_sdk_s3::primitives::DateTime;
+use aws_sdk_s3::Client;
+
+/// Error returned when a `list_objects_v2` page request fails.
+pub type ListError = SdkError<ListObjectsV2Error>;
+
+/// A listed object with the metadata returned by `list_objects_v2`.
+#[derive(Debug, Clone)]
+pub struct ObjectInfo {
+    pub key: String,
+    pub size: Option<i64>,
+    pub last_modified: Option<DateTime>,
+}
+
+/// Lists every object under `prefix`, following continuation tokens until
+/// the listing is exhausted (S3 returns at most 1000 keys per page).
+pub async fn list_all_objects(
+    client: &Client,
+    bucket: &str,
+    prefix: &str,
+) -> Result<Vec<ObjectInfo>, ListError> {
+    let mut objects = Vec::new();
+    let mut pages = client
+        .list_objects_v2()
+        .bucket(bucket)
+        .prefix(prefix)
+        .into_paginator()
+        .send();
+
+    while let Some(page) = pages.next().await {
+        for obj in page?.contents() {
+            if let Some(key) = obj.key() {
+                objects.push(ObjectInfo {
+                    key: key.to_string(),
+                    size: obj.size(),
+                    last_modified: obj.last_modified().cloned(),
+                });
+            }
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Lists every key under `prefix` across all pages.
+pub async fn list_all_keys(
+    client: &Client,
+    bucket: &str,
+    prefix: &str,
+) -> Result<Vec<String>, ListError> {
+    Ok(list_all_objects(client, bucket, prefix)
+        .await?
+        .into_iter()
+        .map(|obj| obj.key)
+        .collect())
+}
@@ -0,0 +1,213 @@
+//! Source and destination directories, either local or on a remote host
+//! reached over SFTP (`user@host:/path`).
+
+use ssh2::{CheckResult, KnownHostFileKind, Session, Sftp};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A directory files are listed in or copied to.
+pub enum Endpoint {
+    Local(PathBuf),
+    Remote(Remote),
+}
+
+/// A directory on a remote host, with an open SFTP channel.
+pub struct Remote {
+    spec: String,
+    dir: PathBuf,
+    sftp: Sftp,
+}
+
+/// What the copy logic needs to know about a file.
+pub struct FileInfo {
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl Endpoint {
+    /// Interprets `spec` as `user@host:/path` when it is valid UTF-8 of that
+    /// shape, connecting over SFTP, and as a local path otherwise.
+    pub fn open(spec: &Path) -> Result<Self, String> {
+        match spec.to_str().and_then(split_remote) {
+            Some((user, host, dir)) => {
+                let sftp = connect(user, host).map_err(|e| format!("{}: {}", host, e))?;
+                Ok(Endpoint::Remote(Remote {
+                    spec: spec.display().to_string(),
+                    dir: PathBuf::from(dir),
+                    sftp,
+                }))
+            }
+            None => Ok(Endpoint::Local(spec.to_path_buf())),
+        }
+    }
+
+    /// The directory this endpoint refers to.
+    pub fn dir(&self) -> &Path {
+        match self {
+            Endpoint::Local(dir) => dir,
+            Endpoint::Remote(remote) => &remote.dir,
+        }
+    }
+
+    pub fn stat(&self, path: &Path) -> io::Result<FileInfo> {
+        match self {
+            Endpoint::Local(_) => {
+                let metadata = fs::metadata(path)?;
+                Ok(FileInfo {
+                    is_dir: metadata.is_dir(),
+                    size: metadata.len(),
+                    modified: metadata.modified().ok(),
+                })
+            }
+            Endpoint::Remote(remote) => {
+                let stat = remote.sftp.stat(path)?;
+                Ok(FileInfo {
+                    is_dir: stat.is_dir(),
+                    size: stat.size.unwrap_or(0),
+                    modified: stat.mtime.map(|t| UNIX_EPOCH + Duration::from_secs(t)),
+                })
+            }
+        }
+    }
+
+    /// Lists the regular files directly inside the directory. Local directories
+    /// are read lazily; remote listings arrive in one batch.
+    pub fn files(&self) -> io::Result<Box<dyn Iterator<Item = PathBuf> + '_>> {
+        match self {
+            Endpoint::Local(dir) => Ok(Box::new(fs::read_dir(dir)?.filter_map(|entry| {
+                entry.ok().and_then(|e| {
+                    let path = e.path();
+                    if path.is_file() {
+                        Some(path)
+                    } else {
+                        None
+                    }
+                })
+            }))),
+            Endpoint::Remote(remote) => Ok(Box::new(
+                remote
+                    .sftp
+                    .readdir(&remote.dir)?
+                    .into_iter()
+                    .filter(|(_, stat)| stat.is_file())
+                    .map(|(path, _)| path),
+            )),
+        }
+    }
+
+    /// Creates the directory and any missing parents.
+    pub fn create_dir_all(&self) -> io::Result<()> {
+        match self {
+            Endpoint::Local(dir) => fs::create_dir_all(dir),
+            Endpoint::Remote(remote) => {
+                let mut missing: Vec<&Path> = remote
+                    .dir
+                    .ancestors()
+                    .take_while(|dir| !dir.as_os_str().is_empty() && remote.sftp.stat(dir).is_err())
+                    .collect();
+                missing.reverse();
+                for dir in missing {
+                    remote.sftp.mkdir(dir, 0o755)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn open_read(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        match self {
+            Endpoint::Local(_) => Ok(Box::new(File::open(path)?)),
+            Endpoint::Remote(remote) => Ok(Box::new(remote.sftp.open(path)?)),
+        }
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write + '_>> {
+        match self {
+            Endpoint::Local(_) => Ok(Box::new(File::create(path)?)),
+            Endpoint::Remote(remote) => Ok(Box::new(remote.sftp.create(path)?)),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Endpoint::Local(dir) => write!(f, "{}", dir.display()),
+            Endpoint::Remote(remote) => write!(f, "{}", remote.spec),
+        }
+    }
+}
+
+/// Copies `src_path` on `src` to `dest_path` on `dest`, returning the bytes copied.
+/// Local-to-local copies go through `fs::copy`; anything involving a remote
+/// endpoint is streamed.
+pub fn copy(src: &Endpoint, src_path: &Path, dest: &Endpoint, dest_path: &Path) -> io::Result<u64> {
+    if let (Endpoint::Local(_), Endpoint::Local(_)) = (src, dest) {
+        return fs::copy(src_path, dest_path);
+    }
+    let mut reader = src.open_read(src_path)?;
+    let mut writer = dest.create(dest_path)?;
+    let bytes = io::copy(&mut reader, &mut writer)?;
+    writer.flush()?;
+    Ok(bytes)
+}
+
+/// Splits `user@host:/path` into its parts. Anything else is a local path,
+/// including, as with scp, any spec with a `/` before its first `:`.
+fn split_remote(spec: &str) -> Option<(&str, &str, &str)> {
+    let (login, dir) = spec.split_once(':')?;
+    if login.contains('/') {
+        return None;
+    }
+    let (user, host) = login.split_once('@')?;
+    if user.is_empty() || host.is_empty() || dir.is_empty() {
+        return None;
+    }
+    Some((user, host, dir))
+}
+
+/// Opens an SFTP channel to `host` as `user`. The host key must already be in
+/// `~/.ssh/known_hosts`; authentication tries the SSH agent, then the default
+/// key files.
+fn connect(user: &str, host: &str) -> Result<Sftp, Box<dyn std::error::Error>> {
+    let tcp = TcpStream::connect((host, 22))?;
+    let mut session = Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.handshake()?;
+
+    let ssh_dir = std::env::var_os("HOME")
+        .map(|home| PathBuf::from(home).join(".ssh"))
+        .ok_or("HOME is not set")?;
+
+    let mut known_hosts = session.known_hosts()?;
+    // A missing known_hosts file leaves the list empty, which fails the check below
+    let _ = known_hosts.read_file(&ssh_dir.join("known_hosts"), KnownHostFileKind::OpenSSH);
+    let (key, _) = session.host_key().ok_or("server sent no host key")?;
+    match known_hosts.check_port(host, 22, key) {
+        CheckResult::Match => {}
+        CheckResult::Mismatch => return Err("host key does not match known_hosts".into()),
+        CheckResult::NotFound => {
+            return Err("host is not in known_hosts; connect once with ssh to add it".into())
+        }
+        CheckResult::Failure => return Err("failed to check the host key".into()),
+    }
+
+    if session.userauth_agent(user).is_err() {
+        for key in ["id_ed25519", "id_ecdsa", "id_rsa"] {
+            let key = ssh_dir.join(key);
+            if key.exists() && session.userauth_pubkey_file(user, None, &key, None).is_ok() {
+                break;
+            }
+        }
+    }
+    if !session.authenticated() {
+        return Err(format!("authentication failed for user '{}'", user).into());
+    }
+
+    Ok(session.sftp()?)
+}
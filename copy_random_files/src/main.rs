@@ -1,105 +1,201 @@
+mod endpoint;
+
 use clap::Parser;
-use indicatif::{ProgressBar, ProgressStyle};
+use cli_common::{format_size, logging, parse_size, parse_time, progress};
+use endpoint::Endpoint;
+use log::{error, info, warn};
 use rand::seq::SliceRandom;
+use rand::Rng;
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 /// Copies a random number of files from one directory to another.
+/// Either directory may be on a remote host, given as `user@host:/path` and
+/// accessed over SFTP.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(group(clap::ArgGroup::new("amount").required(true).args(["number_of_files", "max_bytes"])))]
+#[command(
+    override_usage = "copy_random_files [OPTIONS] <SOURCE_DIRECTORY> <DESTINATION_DIRECTORY> <NUMBER_OF_FILES|--max-bytes <MAX_BYTES>>"
+)]
 struct Args {
-    /// Source directory path
+    /// Source directory path, or user@host:/path for a remote directory
     source_directory: PathBuf,
 
-    /// Destination directory path
+    /// Destination directory path, or user@host:/path for a remote directory
     destination_directory: PathBuf,
 
     /// Number of files to copy
-    number_of_files: usize,
+    number_of_files: Option<usize>,
+
+    /// Copy random files until their total size reaches this budget (e.g. 2GiB, 500MB)
+    #[arg(long, value_parser = parse_size)]
+    max_bytes: Option<u64>,
+
+    /// Directory to write a timestamped log file to
+    #[arg(long)]
+    log_dir: Option<PathBuf>,
+
+    /// Only consider files modified after this time (an age like 7d or 12h, or a date like 2024-05-01)
+    #[arg(long, value_name = "TIME", value_parser = parse_time)]
+    newer_than: Option<SystemTime>,
+
+    /// Only consider files modified before this time (an age like 7d or 12h, or a date like 2024-05-01)
+    #[arg(long, value_name = "TIME", value_parser = parse_time)]
+    older_than: Option<SystemTime>,
+
+    /// Re-read each copy and compare its SHA-256 with the source
+    #[arg(long)]
+    verify: bool,
+
+    /// Number of times to re-copy a file whose checksum does not match
+    #[arg(long, value_name = "N", default_value_t = 3, requires = "verify")]
+    retries: u32,
+
+    /// Report files that fail to copy or verify and continue with the rest
+    #[arg(long)]
+    keep_going: bool,
+
+    /// When fewer files than requested pass the filters, copy all of them with a
+    /// warning instead of failing
+    #[arg(long, requires = "number_of_files")]
+    best_effort: bool,
 }
 
 fn main() {
     // Parse command-line arguments
     let args = Args::parse();
 
+    // Set up file logging if requested
+    if let Some(log_dir) = &args.log_dir {
+        if let Err(e) = logging::init("copy_random_files", log_dir) {
+            eprintln!(
+                "Error: Failed to set up logging in '{}': {}",
+                log_dir.display(),
+                e
+            );
+            std::process::exit(1);
+        }
+    }
+
     // Validate number_of_files is positive
-    if args.number_of_files == 0 {
+    if args.number_of_files == Some(0) {
         eprintln!("Error: Number of files must be a positive integer.");
         print_usage_and_exit();
     }
 
+    // Validate the modification-time window is not empty
+    if let (Some(newer_than), Some(older_than)) = (args.newer_than, args.older_than) {
+        if newer_than >= older_than {
+            eprintln!("Error: --newer-than must be earlier than --older-than.");
+            std::process::exit(1);
+        }
+    }
+
+    // Connect to any remote directories
+    let source = open_endpoint(&args.source_directory);
+    let destination = open_endpoint(&args.destination_directory);
+
     // Check if source directory exists and is a directory
-    if !args.source_directory.exists() || !args.source_directory.is_dir() {
+    if !source.stat(source.dir()).is_ok_and(|info| info.is_dir) {
         eprintln!(
             "Error: Source directory '{}' does not exist or is not a directory.",
-            args.source_directory.display()
+            source
         );
         std::process::exit(1);
     }
 
+    // Refuse to copy into the source directory itself or anywhere below it
+    if let (Endpoint::Local(source_dir), Endpoint::Local(destination_dir)) = (&source, &destination)
+    {
+        match is_same_or_inside(destination_dir, source_dir) {
+            Ok(false) => {}
+            Ok(true) => {
+                eprintln!(
+                    "Error: Destination directory '{}' is the source directory or inside it.",
+                    destination
+                );
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error: Failed to resolve destination directory '{}': {}",
+                    destination, e
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
     // Create destination directory if it doesn't exist
-    if let Err(e) = fs::create_dir_all(&args.destination_directory) {
+    if let Err(e) = destination.create_dir_all() {
         eprintln!(
             "Error: Failed to create destination directory '{}': {}",
-            args.destination_directory.display(),
-            e
+            destination, e
         );
         std::process::exit(1);
     }
 
-    // Read the list of files in the source directory
-    let files = match fs::read_dir(&args.source_directory) {
-        Ok(entries) => entries
-            .filter_map(|entry| {
-                entry.ok().and_then(|e| {
-                    let path = e.path();
-                    if path.is_file() {
-                        Some(path)
-                    } else {
-                        None
-                    }
-                })
-            })
-            .collect::<Vec<PathBuf>>(),
+    // Iterate over the files in the source directory
+    let files = match source.files() {
+        Ok(files) => {
+            files.filter(|path| modified_within(&source, path, args.newer_than, args.older_than))
+        }
         Err(e) => {
-            eprintln!(
-                "Error: Failed to read source directory '{}': {}",
-                args.source_directory.display(),
-                e
-            );
+            eprintln!("Error: Failed to read source directory '{}': {}", source, e);
             std::process::exit(1);
         }
     };
 
-    // Check if there are enough files to copy
-    if files.len() < args.number_of_files {
-        eprintln!(
-            "Error: Not enough files to copy. Available: {}, Requested: {}.",
-            files.len(),
-            args.number_of_files
-        );
-        std::process::exit(1);
-    }
-
-    // Shuffle the list and select the specified number of random files
     let mut rng = rand::thread_rng();
-    let selected_files = files
-        .choose_multiple(&mut rng, args.number_of_files)
-        .cloned()
-        .collect::<Vec<PathBuf>>();
-
-    // Initialize the progress bar
-    let progress_bar = ProgressBar::new(args.number_of_files as u64);
-    progress_bar.set_style(
-        ProgressStyle::with_template(
-            "[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-        )
-        .unwrap()
-        .progress_chars("#>-"),
-    );
-    progress_bar.set_message("Copying files");
+    let (selected_files, progress_bar) = match (args.number_of_files, args.max_bytes) {
+        (Some(number_of_files), _) => {
+            // Sample while reading the directory so only the selection is kept in memory
+            let (mut selected_files, available) =
+                reservoir_sample(files, number_of_files, &mut rng);
+
+            // Check if there are enough files to copy; `available` counts only
+            // files that passed the modification-time filters
+            if available < number_of_files {
+                if !args.best_effort {
+                    eprintln!(
+                        "Error: Not enough files to copy. Available: {}, Requested: {}.",
+                        available, number_of_files
+                    );
+                    std::process::exit(1);
+                }
+                eprintln!(
+                    "Warning: Only {} of the {} requested files are available; copying all of them.",
+                    available, number_of_files
+                );
+                warn!(
+                    "Only {} of {} requested files available, copying all",
+                    available, number_of_files
+                );
+            }
+
+            selected_files.shuffle(&mut rng);
+            let count = selected_files.len() as u64;
+            (selected_files, progress::count_bar(count))
+        }
+        (None, Some(max_bytes)) => {
+            // Shuffle the list and take files while they fit in the size budget
+            let mut files = files.collect::<Vec<PathBuf>>();
+            files.shuffle(&mut rng);
+            let (selected_files, total_bytes) = select_within_budget(&source, files, max_bytes);
+            (selected_files, progress::bytes_bar(total_bytes))
+        }
+        (None, None) => unreachable!("clap requires a file count or --max-bytes"),
+    };
+    progress_bar.set_prefix("Copying files");
 
     // Copy the selected files to the destination directory
+    let mut copied_files = 0;
+    let mut copied_bytes = 0;
+    let mut failed_files = Vec::new();
     for file in selected_files {
         let file_name = match file.file_name() {
             Some(name) => name,
@@ -108,32 +204,250 @@ fn main() {
                     "Warning: Skipping file with invalid name '{}'.",
                     file.display()
                 );
+                warn!("Skipping file with invalid name '{}'", file.display());
                 progress_bar.inc(1);
                 continue;
             }
         };
-        let dest_path = args.destination_directory.join(file_name);
-        if let Err(e) = fs::copy(&file, &dest_path) {
-            eprintln!(
-                "Error: Failed to copy '{}' to '{}': {}",
-                file.display(),
-                dest_path.display(),
-                e
-            );
-            progress_bar.finish_with_message("Failed");
-            std::process::exit(1);
+        let dest_path = destination.dir().join(file_name);
+        let result = if args.verify {
+            copy_verified(&source, &file, &destination, &dest_path, args.retries)
+        } else {
+            endpoint::copy(&source, &file, &destination, &dest_path)
+        };
+        let size = match result {
+            Ok(size) => size,
+            Err(e) if args.keep_going => {
+                eprintln!(
+                    "Error: Failed to copy '{}' to '{}': {}",
+                    file.display(),
+                    dest_path.display(),
+                    e
+                );
+                error!(
+                    "Failed to copy '{}' to '{}': {}",
+                    file.display(),
+                    dest_path.display(),
+                    e
+                );
+                progress_bar.inc(if args.max_bytes.is_some() {
+                    source.stat(&file).map_or(0, |info| info.size)
+                } else {
+                    1
+                });
+                failed_files.push(file);
+                continue;
+            }
+            Err(e) => {
+                eprintln!(
+                    "Error: Failed to copy '{}' to '{}': {}",
+                    file.display(),
+                    dest_path.display(),
+                    e
+                );
+                error!(
+                    "Failed to copy '{}' to '{}': {}",
+                    file.display(),
+                    dest_path.display(),
+                    e
+                );
+                progress_bar.finish_with_message("Failed");
+                std::process::exit(1);
+            }
+        };
+        copied_files += 1;
+        copied_bytes += size;
+        if args.max_bytes.is_some() {
+            progress_bar.inc(size);
+        } else {
+            progress_bar.inc(1);
         }
-        progress_bar.inc(1);
     }
 
     progress_bar.finish_with_message("Done");
 
     println!(
-        "Successfully copied {} files from '{}' to '{}'.",
-        args.number_of_files,
-        args.source_directory.display(),
-        args.destination_directory.display()
+        "Successfully copied {} files ({}) from '{}' to '{}'.",
+        copied_files,
+        format_size(copied_bytes, true),
+        source,
+        destination
+    );
+    info!(
+        "Copied {} files ({} bytes) from '{}' to '{}'",
+        copied_files, copied_bytes, source, destination
     );
+
+    if !failed_files.is_empty() {
+        eprintln!("Failed to copy {} files:", failed_files.len());
+        for file in &failed_files {
+            eprintln!("  {}", file.display());
+        }
+        error!("Failed to copy {} files", failed_files.len());
+        std::process::exit(1);
+    }
+}
+
+/// Copies `src_path` to `dest_path` and checks the copy's SHA-256 against the
+/// source, copying again up to `retries` times while they differ.
+fn copy_verified(
+    src: &Endpoint,
+    src_path: &Path,
+    dest: &Endpoint,
+    dest_path: &Path,
+    retries: u32,
+) -> io::Result<u64> {
+    let expected = hash_file(src, src_path)?;
+    for attempt in 0..=retries {
+        let size = endpoint::copy(src, src_path, dest, dest_path)?;
+        if hash_file(dest, dest_path)? == expected {
+            return Ok(size);
+        }
+        warn!(
+            "Checksum mismatch copying '{}' to '{}' (attempt {} of {})",
+            src_path.display(),
+            dest_path.display(),
+            attempt + 1,
+            retries + 1
+        );
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("checksum mismatch after {} attempts", retries + 1),
+    ))
+}
+
+/// Computes the SHA-256 of a file's content.
+fn hash_file(endpoint: &Endpoint, path: &Path) -> io::Result<Vec<u8>> {
+    let mut reader = BufReader::new(endpoint.open_read(path)?);
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Checks whether the file's modification time falls inside the optional
+/// `(newer_than, older_than)` window. Files whose modification time cannot be
+/// read are left out whenever a bound is given.
+fn modified_within(
+    endpoint: &Endpoint,
+    path: &Path,
+    newer_than: Option<SystemTime>,
+    older_than: Option<SystemTime>,
+) -> bool {
+    if newer_than.is_none() && older_than.is_none() {
+        return true;
+    }
+    let modified = match endpoint.stat(path).and_then(|info| {
+        info.modified
+            .ok_or_else(|| io::Error::other("no modification time"))
+    }) {
+        Ok(modified) => modified,
+        Err(e) => {
+            warn!(
+                "Skipping '{}': cannot read modification time: {}",
+                path.display(),
+                e
+            );
+            return false;
+        }
+    };
+    newer_than.is_none_or(|t| modified > t) && older_than.is_none_or(|t| modified < t)
+}
+
+/// Draws a uniform random sample of `k` items from `items` in a single pass,
+/// holding at most `k` items at a time (reservoir sampling, Algorithm R).
+/// Returns the sample, in no particular order, and the number of items seen.
+fn reservoir_sample<T, I, R>(items: I, k: usize, rng: &mut R) -> (Vec<T>, usize)
+where
+    I: Iterator<Item = T>,
+    R: Rng + ?Sized,
+{
+    let mut reservoir = Vec::with_capacity(k);
+    let mut seen = 0;
+    for item in items {
+        seen += 1;
+        if reservoir.len() < k {
+            reservoir.push(item);
+        } else {
+            let j = rng.gen_range(0..seen);
+            if j < k {
+                reservoir[j] = item;
+            }
+        }
+    }
+    (reservoir, seen)
+}
+
+/// Picks files in order while their cumulative size stays within `max_bytes`.
+/// Files that would overflow the budget are passed over in favour of smaller ones.
+/// Returns the selected files and their total size.
+fn select_within_budget(
+    endpoint: &Endpoint,
+    files: Vec<PathBuf>,
+    max_bytes: u64,
+) -> (Vec<PathBuf>, u64) {
+    let mut selected = Vec::new();
+    let mut total = 0u64;
+    for file in files {
+        let size = match endpoint.stat(&file) {
+            Ok(info) => info.size,
+            Err(e) => {
+                eprintln!("Warning: Skipping '{}': {}", file.display(), e);
+                warn!("Skipping '{}': {}", file.display(), e);
+                continue;
+            }
+        };
+        if total + size <= max_bytes {
+            total += size;
+            selected.push(file);
+            if total == max_bytes {
+                break;
+            }
+        }
+    }
+    (selected, total)
+}
+
+/// Connects to a source or destination directory, exiting on failure.
+fn open_endpoint(spec: &Path) -> Endpoint {
+    match Endpoint::open(spec) {
+        Ok(endpoint) => endpoint,
+        Err(e) => {
+            eprintln!("Error: Failed to connect to '{}': {}", spec.display(), e);
+            error!("Failed to connect to '{}': {}", spec.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Checks whether `path` is `dir` or a descendant of it once both are resolved.
+/// `path` may not exist yet: its deepest existing ancestor is canonicalized and
+/// the remaining components are appended.
+fn is_same_or_inside(path: &Path, dir: &Path) -> io::Result<bool> {
+    let dir = fs::canonicalize(dir)?;
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+
+    let mut existing = path.as_path();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => break,
+        }
+    }
+    let mut resolved = fs::canonicalize(existing)?;
+    for name in rest.iter().rev() {
+        resolved.push(name);
+    }
+
+    Ok(resolved.starts_with(&dir))
 }
 
 fn print_usage_and_exit() {
@@ -1,22 +1,134 @@
+use clap::{Parser, ValueEnum};
+use cli_common::format_size;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::env;
-use std::fs;
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Finds duplicate files in a directory.
+/// By default, reports base names that exist both with and without an extension.
+/// With `--by-content`, reports files with identical content; given a second
+/// directory, reports files in the first that already exist in the second.
+/// With `--perceptual`, reports clusters of visually similar images.
+/// With `--script`, also writes a shell script that removes the content
+/// duplicates, for review before running; nothing is deleted by this tool.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Directory to scan
+    directory: String,
+
+    /// Second directory to compare against (requires --by-content)
+    #[arg(requires = "by_content")]
+    other_directory: Option<String>,
+
+    /// Compare files by SHA-256 of their content instead of by name
+    #[arg(long)]
+    by_content: bool,
+
+    /// Group images whose perceptual hashes (dHash) are within --threshold bits
+    #[arg(long, conflicts_with_all = ["by_content", "other_directory"])]
+    perceptual: bool,
+
+    /// Maximum Hamming distance between hashes of near-duplicate images (0-64)
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 10,
+        requires = "perceptual",
+        value_parser = clap::value_parser!(u32).range(0..=64)
+    )]
+    threshold: u32,
+
+    /// Skip files whose name matches this glob (e.g. '*.tmp', 'Thumbs.db'); may be repeated
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Write a shell script of `rm` commands for the duplicates (requires --by-content)
+    #[arg(long, value_name = "PATH", requires = "by_content")]
+    script: Option<PathBuf>,
+
+    /// Which file of each group the script keeps; with a second directory,
+    /// the copy there is always kept
+    #[arg(long, value_enum, default_value_t = KeepPolicy::First, requires = "script")]
+    keep: KeepPolicy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum KeepPolicy {
+    /// First path in sorted order
+    First,
+    /// Most recently modified file
+    Newest,
+    /// Least recently modified file
+    Oldest,
+}
+
+/// A file that is kept and the duplicates of it that the script removes.
+struct RemovalGroup {
+    keep: PathBuf,
+    remove: Vec<PathBuf>,
+}
 
 fn main() {
-    // Get the directory path from command-line arguments
-    let args: Vec<String> = env::args().collect();
+    // Get the directory paths from command-line arguments
+    let args = Args::parse();
+
+    let exclude = match build_exclude_set(&args.exclude) {
+        Ok(set) => set,
+        Err(e) => {
+            eprintln!("Error: Invalid --exclude pattern: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    if args.len() != 2 {
-        eprintln!("Usage: {} <directory>", args[0]);
-        std::process::exit(1);
+    if args.by_content {
+        let result = match &args.other_directory {
+            Some(other_dir) => report_cross_tree_duplicates(&args.directory, other_dir, &exclude)
+                .map(cross_tree_removals),
+            None => report_content_duplicates(&args.directory, &exclude)
+                .and_then(|groups| content_removals(groups, args.keep)),
+        };
+        let removals = match result {
+            Ok(removals) => removals,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        if let Some(script) = &args.script {
+            if let Err(e) = write_removal_script(script, &removals) {
+                eprintln!("Error writing script '{}': {}", script.display(), e);
+                std::process::exit(1);
+            }
+            let count: usize = removals.iter().map(|group| group.remove.len()).sum();
+            println!(
+                "Wrote a script removing {} files to '{}'. Review it before running.",
+                count,
+                script.display()
+            );
+        }
+        return;
+    }
+
+    if args.perceptual {
+        if let Err(e) = report_perceptual_duplicates(&args.directory, &exclude, args.threshold) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
     }
 
-    let dir_path = &args[1];
+    let dir_path = &args.directory;
 
     // Collect filenames in the directory
-    let filenames = match get_filenames(dir_path) {
-        Ok(names) => names,
+    let filenames = match get_filenames(dir_path, &exclude) {
+        Ok((names, excluded)) => {
+            report_excluded(&exclude, excluded);
+            names
+        }
         Err(e) => {
             eprintln!("Error reading directory '{}': {}", dir_path, e);
             std::process::exit(1);
@@ -68,8 +180,435 @@ fn main() {
     }
 }
 
-fn get_filenames(dir: &str) -> Result<Vec<String>, std::io::Error> {
+/// Prints groups of identical files within `dir` (hashing only same-size files),
+/// largest reclaimable space first, and returns them in that order, each group's paths sorted.
+fn report_content_duplicates(dir: &str, exclude: &GlobSet) -> io::Result<Vec<Vec<PathBuf>>> {
+    let (files, excluded) = get_files_with_sizes(dir, exclude)?;
+    report_excluded(exclude, excluded);
+
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (path, size) in files {
+        by_size.entry(size).or_default().push(path);
+    }
+
+    let mut groups = Vec::new();
+    for (size, paths) in by_size {
+        if paths.len() < 2 {
+            continue;
+        }
+        let mut by_hash: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for path in paths {
+            let hash = hash_file(&path)?;
+            by_hash.entry(hash).or_default().push(path);
+        }
+        groups.extend(
+            by_hash
+                .into_values()
+                .filter(|paths| paths.len() > 1)
+                .map(|paths| (size, paths)),
+        );
+    }
+
+    if groups.is_empty() {
+        println!("No files with identical content found in '{}'.", dir);
+        return Ok(Vec::new());
+    }
+
+    // Keeping one copy per group frees every other copy
+    for (_, paths) in &mut groups {
+        paths.sort();
+    }
+    let reclaimable = |(size, paths): &(u64, Vec<PathBuf>)| size * (paths.len() as u64 - 1);
+    groups.sort_by(|a, b| {
+        reclaimable(b)
+            .cmp(&reclaimable(a))
+            .then_with(|| a.1.cmp(&b.1))
+    });
+    let total: u64 = groups.iter().map(reclaimable).sum();
+    let copies: usize = groups.iter().map(|(_, paths)| paths.len() - 1).sum();
+    println!(
+        "Reclaimable: {} in {} duplicate files across {} groups, keeping one copy of each.",
+        format_size(total, true),
+        copies,
+        groups.len()
+    );
+    println!();
+
+    println!("Files with identical content in '{}':", dir);
+    let mut sorted_groups = Vec::with_capacity(groups.len());
+    for group in groups {
+        println!(
+            "Size: {} each, {} reclaimable",
+            format_size(group.0, true),
+            format_size(reclaimable(&group), true)
+        );
+        let paths = group.1;
+        for path in &paths {
+            println!("  {}", path.display());
+        }
+        println!();
+        sorted_groups.push(paths);
+    }
+
+    Ok(sorted_groups)
+}
+
+/// Prints files in `dir_a` whose content already exists in `dir_b` (hashing only
+/// same-size candidates), largest first, and returns the matching pairs in that order.
+fn report_cross_tree_duplicates(
+    dir_a: &str,
+    dir_b: &str,
+    exclude: &GlobSet,
+) -> io::Result<Vec<(PathBuf, PathBuf)>> {
+    // Every file would match itself, and the removal script would delete them all
+    if fs::canonicalize(dir_a)? == fs::canonicalize(dir_b)? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' and '{}' are the same directory", dir_a, dir_b),
+        ));
+    }
+
+    let (files_b, excluded_b) = get_files_with_sizes(dir_b, exclude)?;
+    let mut b_by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for (path, size) in files_b {
+        b_by_size.entry(size).or_default().push(path);
+    }
+
+    let (mut files_a, excluded_a) = get_files_with_sizes(dir_a, exclude)?;
+    report_excluded(exclude, excluded_a + excluded_b);
+
+    let mut hashes: HashMap<PathBuf, String> = HashMap::new();
+    let mut matches = Vec::new();
+    files_a.sort();
+    for (path_a, size) in files_a {
+        let Some(candidates) = b_by_size.get(&size) else {
+            continue;
+        };
+        let hash_a = hash_file(&path_a)?;
+        for path_b in candidates {
+            if !hashes.contains_key(path_b) {
+                hashes.insert(path_b.clone(), hash_file(path_b)?);
+            }
+            if hashes[path_b] == hash_a && !is_same_file(&path_a, path_b)? {
+                matches.push((path_a.clone(), path_b.clone(), size));
+            }
+        }
+    }
+
+    if matches.is_empty() {
+        println!("No files in '{}' already exist in '{}'.", dir_a, dir_b);
+        return Ok(Vec::new());
+    }
+
+    // Removing each file in `dir_a` once frees its size, however many copies `dir_b` has
+    let mut reclaimable = 0;
+    let mut files = 0;
+    let mut last: Option<&PathBuf> = None;
+    for (path_a, _, size) in &matches {
+        if last != Some(path_a) {
+            reclaimable += size;
+            files += 1;
+            last = Some(path_a);
+        }
+    }
+    println!(
+        "Reclaimable: {} in {} files in '{}' already present in '{}'.",
+        format_size(reclaimable, true),
+        files,
+        dir_a,
+        dir_b
+    );
+    println!();
+
+    // Stable sort keeps each file's matches together, in path order within a size
+    matches.sort_by_key(|(_, _, size)| std::cmp::Reverse(*size));
+
+    println!("Files in '{}' that already exist in '{}':", dir_a, dir_b);
+    for (path_a, path_b, size) in &matches {
+        println!("  {}", path_a.display());
+        println!("  {}", path_b.display());
+        println!("  Size: {}", format_size(*size, true));
+        println!();
+    }
+
+    Ok(matches.into_iter().map(|(a, b, _)| (a, b)).collect())
+}
+
+/// Picks the file to keep in each group according to `policy`.
+fn content_removals(
+    groups: Vec<Vec<PathBuf>>,
+    policy: KeepPolicy,
+) -> io::Result<Vec<RemovalGroup>> {
+    let mut removals = Vec::with_capacity(groups.len());
+    for mut paths in groups {
+        let keep_index = match policy {
+            KeepPolicy::First => 0,
+            KeepPolicy::Newest | KeepPolicy::Oldest => {
+                let mut times = Vec::with_capacity(paths.len());
+                for path in &paths {
+                    times.push(fs::metadata(path)?.modified()?);
+                }
+                // Ties go to the earlier path, so the choice doesn't depend on scan order
+                let indices = 0..paths.len();
+                if policy == KeepPolicy::Newest {
+                    indices.max_by_key(|&i| (times[i], std::cmp::Reverse(i)))
+                } else {
+                    indices.min_by_key(|&i| (times[i], i))
+                }
+                .unwrap_or(0)
+            }
+        };
+        let keep = paths.remove(keep_index);
+        removals.push(RemovalGroup {
+            keep,
+            remove: paths,
+        });
+    }
+    Ok(removals)
+}
+
+/// Keeps the copy in the second directory and removes each matching file in the
+/// first one once, even when it matches several files there.
+fn cross_tree_removals(matches: Vec<(PathBuf, PathBuf)>) -> Vec<RemovalGroup> {
+    let mut removals: Vec<RemovalGroup> = Vec::new();
+    for (path_a, path_b) in matches {
+        if removals
+            .last()
+            .is_some_and(|group| group.remove[0] == path_a)
+        {
+            continue;
+        }
+        removals.push(RemovalGroup {
+            keep: path_b,
+            remove: vec![path_a],
+        });
+    }
+    removals
+}
+
+/// Writes a POSIX shell script that removes the duplicates group by group,
+/// with a comment naming the file kept for each group. Paths are written as
+/// absolute paths so the script can be run from any directory.
+fn write_removal_script(path: &Path, removals: &[RemovalGroup]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "#!/bin/sh")?;
+    writeln!(
+        out,
+        "# Generated by find_duplicates. Review before running."
+    )?;
+    writeln!(out, "set -e")?;
+    for group in removals {
+        writeln!(out)?;
+        // Debug formatting escapes newlines, which would otherwise end the comment
+        writeln!(out, "# keep: {:?}", absolute_path(&group.keep)?)?;
+        for file in &group.remove {
+            writeln!(out, "rm -- {}", shell_quote(&absolute_path(file)?)?)?;
+        }
+    }
+    out.flush()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+/// Resolves the directory holding `path`, keeping the file name itself as is so
+/// that a symlink is removed rather than the file it points to.
+fn absolute_path(path: &Path) -> io::Result<PathBuf> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    Ok(fs::canonicalize(parent)?.join(name))
+}
+
+/// Single-quotes a path for the shell, so spaces and metacharacters are taken literally.
+/// Non-UTF-8 paths are refused, since a lossy conversion would name a different file.
+fn shell_quote(path: &Path) -> io::Result<String> {
+    let path = path.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("cannot write non-UTF-8 path {:?} to the script", path),
+        )
+    })?;
+    Ok(format!("'{}'", path.replace('\'', "'\\''")))
+}
+
+/// Reports clusters of images in `dir` whose perceptual hashes are within
+/// `threshold` bits of each other, with the distance between each pair.
+/// Files that are not decodable images are skipped.
+fn report_perceptual_duplicates(dir: &str, exclude: &GlobSet, threshold: u32) -> io::Result<()> {
+    let (files, excluded) = get_files_with_sizes(dir, exclude)?;
+    report_excluded(exclude, excluded);
+
+    let mut hashes: Vec<(PathBuf, u64)> = Vec::new();
+    for (path, _) in files {
+        if image::ImageFormat::from_path(&path).is_err() {
+            continue;
+        }
+        match dhash(&path) {
+            Ok(hash) => hashes.push((path, hash)),
+            Err(e) => eprintln!("Skipping '{}': {}", path.display(), e),
+        }
+    }
+    hashes.sort();
+
+    // Link every pair within the threshold; clusters are the connected groups
+    let mut parent: Vec<usize> = (0..hashes.len()).collect();
+    let mut close_pairs = Vec::new();
+    for i in 0..hashes.len() {
+        for j in i + 1..hashes.len() {
+            let distance = (hashes[i].1 ^ hashes[j].1).count_ones();
+            if distance <= threshold {
+                close_pairs.push((i, j, distance));
+                let (root_i, root_j) = (find_root(&mut parent, i), find_root(&mut parent, j));
+                parent[root_j] = root_i;
+            }
+        }
+    }
+
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..hashes.len() {
+        let root = find_root(&mut parent, i);
+        clusters.entry(root).or_default().push(i);
+    }
+    let mut clusters: Vec<Vec<usize>> = clusters
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .collect();
+    clusters.sort();
+
+    if clusters.is_empty() {
+        println!(
+            "No near-duplicate images found in '{}' (threshold {}).",
+            dir, threshold
+        );
+        return Ok(());
+    }
+
+    println!(
+        "Near-duplicate images in '{}' (threshold {}):",
+        dir, threshold
+    );
+    for (n, members) in clusters.iter().enumerate() {
+        println!("Cluster {}:", n + 1);
+        for &i in members {
+            println!("  {}", hashes[i].0.display());
+        }
+        println!("  Distances:");
+        for &(i, j, distance) in &close_pairs {
+            if members.contains(&i) {
+                println!(
+                    "    {} <-> {}: {}",
+                    hashes[i].0.display(),
+                    hashes[j].0.display(),
+                    distance
+                );
+            }
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Finds the representative of `i`'s cluster, flattening the path as it goes.
+fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Computes a 64-bit difference hash: the image is shrunk to 9x8 grayscale and
+/// each bit records whether a pixel is brighter than its right-hand neighbour.
+fn dhash(path: &Path) -> image::ImageResult<u64> {
+    let small = image::open(path)?
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Ok(hash)
+}
+
+/// Returns whether two paths resolve to the same file, e.g. through a symlink in
+/// one directory pointing into the other.
+fn is_same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    Ok(fs::canonicalize(a)? == fs::canonicalize(b)?)
+}
+
+/// Computes the SHA-256 of a file's content as a hex string.
+fn hash_file(path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Compiles the `--exclude` globs into a single matcher.
+fn build_exclude_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    builder.build()
+}
+
+/// Checks whether the file name of `path` matches any `--exclude` glob.
+fn is_excluded(path: &Path, exclude: &GlobSet) -> bool {
+    path.file_name()
+        .is_some_and(|name| exclude.is_match(Path::new(name)))
+}
+
+/// Prints how many files the `--exclude` globs filtered out of the scan.
+fn report_excluded(exclude: &GlobSet, excluded: usize) {
+    if !exclude.is_empty() {
+        println!("Excluded {} files matching --exclude.", excluded);
+        println!();
+    }
+}
+
+/// Lists the files in `dir` with their sizes, skipping excluded names.
+/// Returns the files and the number of files excluded.
+fn get_files_with_sizes(dir: &str, exclude: &GlobSet) -> io::Result<(Vec<(PathBuf, u64)>, usize)> {
+    let mut files = Vec::new();
+    let mut excluded = 0;
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        // Check if the entry is a file
+        if path.is_file() {
+            if is_excluded(&path, exclude) {
+                excluded += 1;
+                continue;
+            }
+            let size = entry.metadata()?.len();
+            files.push((path, size));
+        }
+    }
+
+    Ok((files, excluded))
+}
+
+fn get_filenames(dir: &str, exclude: &GlobSet) -> Result<(Vec<String>, usize), std::io::Error> {
     let mut filenames = Vec::new();
+    let mut excluded = 0;
 
     for entry in fs::read_dir(dir)? {
         let entry = entry?;
@@ -77,11 +616,15 @@ fn get_filenames(dir: &str) -> Result<Vec<String>, std::io::Error> {
 
         // Check if the entry is a file
         if path.is_file() {
+            if is_excluded(&path, exclude) {
+                excluded += 1;
+                continue;
+            }
             if let Some(filename) = path.file_name().and_then(|f| f.to_str()) {
                 filenames.push(filename.to_string());
             }
         }
     }
 
-    Ok(filenames)
+    Ok((filenames, excluded))
 }
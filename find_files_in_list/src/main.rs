@@ -1,14 +1,105 @@
-use indicatif::{ProgressBar, ProgressStyle};
-use std::collections::HashMap;
-use std::env;
+use clap::Parser;
+use cli_common::{format_size, logging, progress};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, File};
-use std::io::{BufRead, BufReader, Result};
+use std::io::{BufRead, BufReader, BufWriter, Result};
 use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
 use walkdir::{DirEntry, Error as WalkDirError, WalkDir};
 
-/// Builds a map of file stems (lowercased) -> full path of the *first* encountered file.
+/// Copies the files named in a list from the current directory tree into an output directory.
+/// Files are matched by stem (case-insensitive), so list lines may omit or differ in extension.
+/// With `--contains`, every file whose name contains a list line is copied instead.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// File with one name per line
+    list_file: String,
+
+    /// Directory the matched files are copied into
+    output_directory: String,
+
+    /// Only process list lines starting with this prefix
+    optional_prefix: Option<String>,
+
+    /// Directory to write a timestamped log file to
+    #[arg(long)]
+    log_dir: Option<PathBuf>,
+
+    /// Cache the stem map in this file and reuse it while it is newer than the directory.
+    /// Only the top-level directory's mtime is checked; use --refresh-cache after
+    /// changes below the top level
+    #[arg(long)]
+    map_cache: Option<PathBuf>,
+
+    /// Rebuild the stem map even if the cache is up to date
+    #[arg(long, requires = "map_cache")]
+    refresh_cache: bool,
+
+    /// Normalize names to Unicode NFC before matching, so composed and decomposed
+    /// (macOS-style) forms of the same name match; pass `--normalize false` to disable
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    normalize: bool,
+
+    /// When several files share a stem, prefer these extensions in order (e.g. jpg,png)
+    /// instead of taking the first file found
+    #[arg(long, value_delimiter = ',')]
+    prefer_ext: Vec<String>,
+
+    /// Stop after copying this many files
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+
+    /// Report list entries matching more than one file, then exit without copying
+    #[arg(long)]
+    check: bool,
+
+    /// Copy every file whose name contains the list line (case-insensitive), instead of
+    /// matching by stem; checks each line against every file, so it is slow on large trees
+    #[arg(long, conflicts_with = "check")]
+    contains: bool,
+}
+
+/// Settings that change how the stem map is built.
+#[derive(Serialize, Deserialize, PartialEq)]
+struct MapOptions {
+    normalize: bool,
+    prefer_ext: Vec<String>,
+}
+
+impl MapOptions {
+    /// Position of the path's extension in the preference list; unlisted
+    /// extensions rank after all listed ones.
+    fn ext_rank(&self, path: &Path) -> usize {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(|ext| {
+                self.prefer_ext
+                    .iter()
+                    .position(|p| p.trim_start_matches('.').eq_ignore_ascii_case(ext))
+            })
+            .unwrap_or(self.prefer_ext.len())
+    }
+}
+
+/// On-disk form of the stem map, tied to the directory and options it was built with.
+#[derive(Serialize, Deserialize)]
+struct StemMapCache {
+    root: PathBuf,
+    options: MapOptions,
+    stems: HashMap<String, Vec<PathBuf>>,
+}
+
+/// Builds a map of file stems (lowercased) -> full paths of every file with that stem.
+/// Candidates are ordered by `--prefer-ext` rank, then in the order they were found,
+/// so the first one is the file to copy.
 /// Also collects any WalkDir errors into a separate Vec so we can report them.
-fn build_stem_map(root_dir: &str) -> (HashMap<String, PathBuf>, Vec<WalkDirError>) {
+fn build_stem_map(
+    root_dir: &str,
+    options: &MapOptions,
+) -> (HashMap<String, Vec<PathBuf>>, Vec<WalkDirError>) {
     let mut entries = Vec::new();
     let mut errors = Vec::new();
 
@@ -27,51 +118,96 @@ fn build_stem_map(root_dir: &str) -> (HashMap<String, PathBuf>, Vec<WalkDirError
         .collect();
 
     // Create a progress bar for building the map
-    let pb = ProgressBar::new(entries.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{spinner:.yellow} Building map [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) - {msg}")
-            .unwrap()
-            .progress_chars("##-"),
-    );
+    let pb = progress::count_bar(entries.len() as u64);
+    pb.set_prefix("Building map");
 
-    let mut map = HashMap::new();
+    let mut map: HashMap<String, Vec<PathBuf>> = HashMap::new();
 
     // Process each file entry, extracting the stem and storing in the map
     for entry in entries {
         let path = entry.path();
         if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
-            let stem_lower = stem.to_lowercase();
-            // Insert if not already present
-            map.entry(stem_lower).or_insert_with(|| path.to_path_buf());
+            let stem_lower = stem_key(stem, options.normalize);
+            map.entry(stem_lower).or_default().push(path.to_path_buf());
         }
         pb.inc(1);
     }
 
+    // Stable sort keeps discovery order among equally preferred extensions
+    for candidates in map.values_mut() {
+        candidates.sort_by_key(|path| options.ext_rank(path));
+    }
+
     pb.finish_with_message("Stem map built.");
 
     (map, errors)
 }
 
+/// Turns a file stem into a map key: lowercased and, if requested, NFC-normalized.
+fn stem_key(stem: &str, normalize: bool) -> String {
+    if normalize {
+        stem.nfc().collect::<String>().to_lowercase()
+    } else {
+        stem.to_lowercase()
+    }
+}
+
+/// Loads a cached stem map if it was built for `root_dir` with the same options
+/// and the cache file is newer than the directory. Only the top-level directory's
+/// mtime is checked, so changes deep inside the tree need `--refresh-cache`.
+fn load_stem_map_cache(
+    cache_path: &Path,
+    root_dir: &str,
+    options: &MapOptions,
+) -> Option<HashMap<String, Vec<PathBuf>>> {
+    let cache_modified = fs::metadata(cache_path).ok()?.modified().ok()?;
+    let root_modified = fs::metadata(root_dir).ok()?.modified().ok()?;
+    if cache_modified < root_modified {
+        return None;
+    }
+
+    let file = File::open(cache_path).ok()?;
+    let cache: StemMapCache = serde_json::from_reader(BufReader::new(file)).ok()?;
+    if cache.root != fs::canonicalize(root_dir).ok()? || cache.options != *options {
+        return None;
+    }
+    Some(cache.stems)
+}
+
+/// Writes the stem map to `cache_path` as JSON.
+fn save_stem_map_cache(
+    cache_path: &Path,
+    root_dir: &str,
+    options: MapOptions,
+    stems: &HashMap<String, Vec<PathBuf>>,
+) -> Result<()> {
+    let cache = StemMapCache {
+        root: fs::canonicalize(root_dir)?,
+        options,
+        stems: stems.clone(),
+    };
+    let writer = BufWriter::new(File::create(cache_path)?);
+    serde_json::to_writer(writer, &cache)?;
+    Ok(())
+}
+
 fn main() -> Result<()> {
     // Command-line usage:
-    //   cargo run -- <list_file> <output_directory> [optional_prefix]
+    //   cargo run -- <list_file> <output_directory> [optional_prefix] [--log-dir <dir>]
     //
     // If [optional_prefix] is present, only lines in <list_file> that start with that prefix
     // are processed. Otherwise, all lines.
 
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 3 {
-        eprintln!(
-            "Usage: {} <list_file> <output_directory> [optional_prefix]",
-            args[0]
-        );
-        std::process::exit(1);
-    }
+    let args = Args::parse();
+
+    let list_file = &args.list_file;
+    let output_dir = &args.output_directory;
+    let optional_prefix = args.optional_prefix.as_deref();
 
-    let list_file = &args[1];
-    let output_dir = &args[2];
-    let optional_prefix = args.get(3).map(|s| s.as_str());
+    // Set up file logging if requested
+    if let Some(log_dir) = &args.log_dir {
+        logging::init("find_files_in_list", log_dir)?;
+    }
 
     // 1. Read lines from list_file, collecting line-read errors
     let file = File::open(list_file)?;
@@ -100,6 +236,7 @@ fn main() -> Result<()> {
         eprintln!("Errors occurred while reading lines from '{list_file}':");
         for (i, err) in line_read_errors.iter().enumerate() {
             eprintln!("  {}. {}", i + 1, err);
+            error!("Failed to read line from '{list_file}': {err}");
         }
         // Decide if you want to stop or continue. Here we continue.
     }
@@ -109,67 +246,142 @@ fn main() -> Result<()> {
         lines.retain(|line| line.starts_with(prefix));
     }
 
-    // 3. Build the stem map of the current directory (.) and collect any WalkDir errors
-    let (stem_map, walkdir_errors) = build_stem_map(".");
+    // 3. Build the stem map of the current directory (.) and collect any WalkDir errors,
+    //    or reuse a cached map when one is available
+    let map_options = MapOptions {
+        normalize: args.normalize,
+        prefer_ext: args.prefer_ext.clone(),
+    };
+    let cached = match &args.map_cache {
+        Some(cache_path) if !args.refresh_cache => {
+            load_stem_map_cache(cache_path, ".", &map_options)
+        }
+        _ => None,
+    };
+    let (stem_map, walkdir_errors) = match cached {
+        Some(stem_map) => {
+            println!("Loaded stem map ({} entries) from cache.", stem_map.len());
+            (stem_map, Vec::new())
+        }
+        None => {
+            let (stem_map, walkdir_errors) = build_stem_map(".", &map_options);
+            if let Some(cache_path) = &args.map_cache {
+                if let Err(e) = save_stem_map_cache(cache_path, ".", map_options, &stem_map) {
+                    eprintln!("Failed to write stem map cache {cache_path:?}: {e}");
+                    warn!("Failed to write stem map cache {cache_path:?}: {e}");
+                }
+            }
+            (stem_map, walkdir_errors)
+        }
+    };
 
     // Report WalkDir errors, if any
     if !walkdir_errors.is_empty() {
         eprintln!("Errors occurred while scanning the directory for files:");
         for (i, err) in walkdir_errors.iter().enumerate() {
             eprintln!("  {}. {}", i + 1, err);
+            error!("Failed to scan directory entry: {err}");
         }
         // Again, decide if you want to stop here or continue. We'll continue.
     }
 
+    if args.check {
+        let ambiguous = report_ambiguous_entries(&lines, &stem_map, args.normalize);
+        if ambiguous > 0 {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // For --contains, every file name is a candidate for every line
+    let mut name_candidates: Vec<(String, &PathBuf)> = Vec::new();
+    if args.contains {
+        name_candidates = stem_map
+            .values()
+            .flatten()
+            .map(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                (stem_key(&name, args.normalize), path)
+            })
+            .collect();
+        name_candidates.sort_by(|a, b| a.1.cmp(b.1));
+        eprintln!(
+            "Warning: --contains checks each of {} list entries against {} files, which can be slow. \
+             Narrow it with a prefix argument or by running from a smaller directory.",
+            lines.len(),
+            name_candidates.len()
+        );
+        warn!(
+            "--contains: {} list entries x {} files",
+            lines.len(),
+            name_candidates.len()
+        );
+    }
+
     // Ensure the output directory exists
     fs::create_dir_all(output_dir)?;
 
     // 4. Prepare a progress bar for the copy phase
-    let pb = ProgressBar::new(lines.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{spinner:.green} Copying files [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) - {msg}")
-            .unwrap()
-            .progress_chars("##-"),
-    );
+    let pb = progress::count_bar(lines.len() as u64);
+    pb.set_prefix("Copying files");
 
-    // 5. Copy files according to the list
+    // 5. Copy files according to the list, tallying copies per extension
+    let mut copied_by_ext: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+    let mut copied = 0;
+    let mut processed = 0;
     for line in &lines {
+        if args.limit.is_some_and(|limit| copied >= limit) {
+            break;
+        }
+
         // Show which file is being processed
         pb.set_message(format!("Searching: {line}"));
 
+        if args.contains {
+            let needle = stem_key(line, args.normalize);
+            let matches: Vec<&PathBuf> = name_candidates
+                .iter()
+                .filter(|(name, _)| name.contains(&needle))
+                .map(|(_, path)| *path)
+                .collect();
+            if matches.is_empty() {
+                eprintln!("No file name containing '{line}' found in the directory.");
+                warn!("No file name containing '{line}'");
+            }
+            for found_path in matches {
+                if args.limit.is_some_and(|limit| copied >= limit) {
+                    break;
+                }
+                pb.set_message(format!(
+                    "Copying: {}",
+                    found_path.file_name().unwrap_or_default().to_string_lossy()
+                ));
+                if copy_found_file(found_path, output_dir, &mut copied_by_ext) {
+                    copied += 1;
+                }
+            }
+            pb.inc(1);
+            processed += 1;
+            continue;
+        }
+
         // Extract the stem from the list line itself (in case user wrote "myfile.txt")
         let line_path = Path::new(line);
         let line_stem_raw = match line_path.file_stem() {
             Some(s) => s.to_string_lossy().to_string(),
             None => line.clone(), // fallback if no stem
         };
-        let line_stem_lower = line_stem_raw.to_lowercase();
+        let line_stem_lower = stem_key(&line_stem_raw, args.normalize);
 
         // Lookup in the map
-        if let Some(found_path) = stem_map.get(&line_stem_lower) {
+        if let Some(found_path) = stem_map.get(&line_stem_lower).and_then(|c| c.first()) {
             // found_path is the actual file on disk
-            let file_name = found_path
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string();
-
-            let mut dest_path = PathBuf::from(output_dir);
-            dest_path.push(&file_name);
-
-            // Optional: skip if the file already exists in the destination
-            if dest_path.exists() {
-                eprintln!(
-                    "Skipping, file already exists in destination: {:?}",
-                    dest_path
-                );
-            } else {
-                // Copy the file
-                pb.set_message(format!("Copying: {file_name}"));
-                if let Err(e) = fs::copy(found_path, &dest_path) {
-                    eprintln!("Failed to copy '{found_path:?}' to '{dest_path:?}': {e}");
-                }
+            pb.set_message(format!(
+                "Copying: {}",
+                found_path.file_name().unwrap_or_default().to_string_lossy()
+            ));
+            if copy_found_file(found_path, output_dir, &mut copied_by_ext) {
+                copied += 1;
             }
         } else {
             // If not found, report it
@@ -177,12 +389,135 @@ fn main() -> Result<()> {
                 "No matching file for '{}' (stem '{}') found in the directory.",
                 line, line_stem_lower
             );
+            warn!("No matching file for '{line}' (stem '{line_stem_lower}')");
         }
 
         pb.inc(1);
+        processed += 1;
     }
 
     pb.finish_with_message("All done copying!");
+    info!("Processed {processed} list entries into '{output_dir}'");
+
+    // Report what --limit left behind
+    let unprocessed = lines.len() - processed;
+    if unprocessed > 0 {
+        println!("Stopped after {copied} copies; {unprocessed} list entries were not processed.");
+        info!("Stopped at --limit; {unprocessed} list entries were not processed");
+    }
+
+    print_extension_summary(&copied_by_ext);
 
     Ok(())
 }
+
+/// Copies `found_path` into `output_dir`, skipping it if a file of that name is
+/// already there, and tallies the copy by extension. Returns whether it was copied.
+fn copy_found_file(
+    found_path: &Path,
+    output_dir: &str,
+    copied_by_ext: &mut BTreeMap<String, (usize, u64)>,
+) -> bool {
+    let file_name = found_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    let mut dest_path = PathBuf::from(output_dir);
+    dest_path.push(&file_name);
+
+    // Optional: skip if the file already exists in the destination
+    if dest_path.exists() {
+        eprintln!(
+            "Skipping, file already exists in destination: {:?}",
+            dest_path
+        );
+        warn!("Skipping, file already exists in destination: {dest_path:?}");
+        return false;
+    }
+
+    // Copy the file
+    match fs::copy(found_path, &dest_path) {
+        Ok(bytes) => {
+            let ext = found_path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| "(none)".to_string());
+            let tally = copied_by_ext.entry(ext).or_default();
+            tally.0 += 1;
+            tally.1 += bytes;
+            true
+        }
+        Err(e) => {
+            eprintln!("Failed to copy '{found_path:?}' to '{dest_path:?}': {e}");
+            error!("Failed to copy '{found_path:?}' to '{dest_path:?}': {e}");
+            false
+        }
+    }
+}
+
+/// Prints each list entry whose stem matches more than one file, with its
+/// candidates in the order they would be preferred. Returns how many there were.
+fn report_ambiguous_entries(
+    lines: &[String],
+    stem_map: &HashMap<String, Vec<PathBuf>>,
+    normalize: bool,
+) -> usize {
+    let mut ambiguous = 0;
+    for line in lines {
+        let stem = Path::new(line)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| line.clone());
+        let Some(candidates) = stem_map.get(&stem_key(&stem, normalize)) else {
+            continue;
+        };
+        if candidates.len() > 1 {
+            ambiguous += 1;
+            println!("'{line}' matches {} files:", candidates.len());
+            for candidate in candidates {
+                println!("  {}", candidate.display());
+            }
+        }
+    }
+
+    if ambiguous == 0 {
+        println!("No ambiguous entries among {} list entries.", lines.len());
+    } else {
+        println!("{ambiguous} of {} list entries are ambiguous.", lines.len());
+        warn!("{ambiguous} of {} list entries are ambiguous", lines.len());
+    }
+    ambiguous
+}
+
+/// Prints how many files, and how many bytes, were copied per extension.
+fn print_extension_summary(copied_by_ext: &BTreeMap<String, (usize, u64)>) {
+    if copied_by_ext.is_empty() {
+        println!("No files copied.");
+        return;
+    }
+    println!("Copied by extension:");
+    for (ext, (count, bytes)) in copied_by_ext {
+        println!("  {ext}: {count} files, {}", format_size(*bytes, true));
+        info!("Copied {count} .{ext} files ({bytes} bytes)");
+    }
+    let (total_count, total_bytes) = copied_by_ext
+        .values()
+        .fold((0, 0), |(c, b), (count, bytes)| (c + count, b + bytes));
+    println!(
+        "  total: {total_count} files, {}",
+        format_size(total_bytes, true)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stem_key_matches_composed_and_decomposed_names_when_normalizing() {
+        assert_eq!(stem_key("Cafe\u{301}", true), stem_key("Caf\u{e9}", true));
+        assert_ne!(stem_key("Cafe\u{301}", false), stem_key("Caf\u{e9}", false));
+    }
+}
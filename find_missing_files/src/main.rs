@@ -1,67 +1,147 @@
+use clap::Parser;
+use rayon::prelude::*;
 use std::collections::HashSet;
-use std::env;
 use std::fs;
+use std::path::PathBuf;
 
-fn main() {
-    // Get command-line arguments for directory paths, postfix, and expected file count
-    let args: Vec<String> = env::args().collect();
+/// Finds images in dir1 that are missing some of their `<base><postfix><index>.jpg`
+/// derivatives. Extra derivative directories may follow <EXPECTED_COUNT>; a
+/// derivative counts as present if it exists in any of them.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Directory with the original images
+    dir1: String,
 
-    if args.len() != 5 {
-        eprintln!(
-            "Usage: {} <dir1> <dir2> <postfix> <expected_count>",
-            args[0]
-        );
-        std::process::exit(1);
+    /// Directory with the derivatives
+    dir2: String,
+
+    /// Separator between the base name and the index
+    postfix: String,
+
+    /// Number of derivatives expected per image (indices 0 to count - 1)
+    #[arg(value_parser = parse_expected_count)]
+    expected_count: usize,
+
+    /// More directories to look for derivatives in
+    more_dir2: Vec<String>,
+
+    /// Print this command for each missing file instead of the report, replacing
+    /// {base}, {postfix}, {index} and {ext}; {base} and {postfix} are shell-quoted
+    /// (e.g. "crop {base}.jpg {index} -o {base}{postfix}{index}.{ext}")
+    #[arg(long, value_name = "TEMPLATE")]
+    cmd_template: Option<String>,
+}
+
+fn parse_expected_count(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err("expected count must be a positive integer".to_string()),
     }
+}
 
-    let dir1 = &args[1];
-    let dir2 = &args[2];
-    let postfix = &args[3];
-    let expected_count: usize = match args[4].parse() {
-        Ok(n) if n > 0 => n,
-        _ => {
-            eprintln!("Error: Expected count must be a positive integer.");
-            std::process::exit(1);
-        }
-    };
+fn main() {
+    let args = Args::parse();
+
+    let dir1 = &args.dir1;
+    let mut dir2s = vec![&args.dir2];
+    dir2s.extend(&args.more_dir2);
+    let dir2 = dir2s
+        .iter()
+        .map(|d| d.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let postfix = &args.postfix;
+    let expected_count = args.expected_count;
 
-    // Collect base filenames from dir1
-    let dir1_basenames = match get_basenames(dir1) {
+    // Scan dir1 and every derivative directory concurrently
+    let (dir1_result, dir2_results) = rayon::join(
+        || get_basenames(dir1),
+        || {
+            dir2s
+                .par_iter()
+                .map(|dir| (*dir, get_filenames(dir)))
+                .collect::<Vec<_>>()
+        },
+    );
+
+    // Collect base filenames from dir1, sorted so the report is deterministic
+    let mut dir1_basenames = match dir1_result {
         Ok(names) => names,
         Err(e) => {
             eprintln!("Error reading directory '{}': {}", dir1, e);
             std::process::exit(1);
         }
     };
+    dir1_basenames.par_sort_unstable();
     eprintln!("Total files in dir1: {}", dir1_basenames.len());
 
-    // Collect filenames from dir2
-    let dir2_filenames = match get_filenames(dir2) {
-        Ok(names) => names,
-        Err(e) => {
-            eprintln!("Error reading directory '{}': {}", dir2, e);
-            std::process::exit(1);
-        }
-    };
-    eprintln!("Total files in dir2: {}", dir2_filenames.len());
+    // Collect filenames from each derivative directory into a HashSet for quick lookup
+    let mut dir2_filenames_sets: Vec<(&String, HashSet<String>)> = Vec::new();
+    for (dir, result) in dir2_results {
+        let filenames = match result {
+            Ok(names) => names,
+            Err(e) => {
+                eprintln!("Error reading directory '{}': {}", dir, e);
+                std::process::exit(1);
+            }
+        };
+        eprintln!("Total files in {}: {}", dir, filenames.len());
+        dir2_filenames_sets.push((dir, filenames.into_par_iter().collect()));
+    }
 
-    // Create a HashSet for quick lookup
-    let dir2_filenames_set: HashSet<String> = dir2_filenames.into_iter().collect();
+    // Check for each basename if all expected files exist in one of the dir2s,
+    // remembering which directory satisfied each one
+    let results: Vec<_> = dir1_basenames
+        .into_par_iter()
+        .map(|basename| {
+            let mut missing_files = Vec::new();
+            let mut found_files = Vec::new();
+            for i in 0..expected_count {
+                let filename = format!("{}{}{}.jpg", basename, postfix, i);
+                match dir2_filenames_sets
+                    .iter()
+                    .find(|(_, set)| set.contains(&filename))
+                {
+                    Some((dir, _)) => found_files.push((filename, *dir)),
+                    None => missing_files.push((i, filename)),
+                }
+            }
+            (basename, missing_files, found_files)
+        })
+        .collect();
 
-    // Check for each basename if all expected files exist in dir2
     let mut files_with_missing = Vec::new();
+    let mut found_locations = Vec::new();
+    for (basename, missing_files, found_files) in results {
+        if !missing_files.is_empty() {
+            files_with_missing.push((basename.clone(), missing_files));
+        }
+        if !found_files.is_empty() {
+            found_locations.push((basename, found_files));
+        }
+    }
 
-    for basename in dir1_basenames {
-        let mut missing_files = Vec::new();
-        for i in 0..expected_count {
-            let filename = format!("{}{}{}.jpg", basename, postfix, i);
-            if !dir2_filenames_set.contains(&filename) {
-                missing_files.push(filename);
+    // Emit only the regeneration commands, so the output can be piped to a shell
+    if let Some(template) = &args.cmd_template {
+        for (basename, missing_files) in &files_with_missing {
+            for (index, _) in missing_files {
+                println!("{}", fill_template(template, basename, postfix, *index));
             }
         }
-        if !missing_files.is_empty() {
-            files_with_missing.push((basename, missing_files));
+        return;
+    }
+
+    // With several derivative directories, show where each file was found
+    if dir2s.len() > 1 {
+        println!("Derivative locations:");
+        for (basename, found_files) in &found_locations {
+            println!("Base name: {}", basename);
+            for (file, dir) in found_files {
+                println!("  {} -> {}", file, dir);
+            }
         }
+        println!();
     }
 
     // Display the result
@@ -78,7 +158,7 @@ fn main() {
         for (basename, missing_files) in files_with_missing {
             println!("Base name: {}", basename);
             println!("Missing files:");
-            for file in missing_files {
+            for (_, file) in missing_files {
                 println!("  {}", file);
             }
             println!();
@@ -86,50 +166,92 @@ fn main() {
     }
 }
 
-fn get_basenames(dir: &str) -> Result<Vec<String>, std::io::Error> {
-    let mut basenames = Vec::new();
+/// Substitutes a missing derivative's parts into a `--cmd-template` in a single
+/// pass, shell-quoting `{base}` and `{postfix}` so file names are taken literally.
+fn fill_template(template: &str, basename: &str, postfix: &str, index: usize) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = if rest.starts_with("{base}") {
+            Some(shell_quote(basename))
+        } else if rest.starts_with("{postfix}") {
+            Some(shell_quote(postfix))
+        } else if rest.starts_with("{index}") {
+            Some(index.to_string())
+        } else if rest.starts_with("{ext}") {
+            Some("jpg".to_string())
+        } else {
+            None
+        };
+        match value {
+            Some(value) => {
+                out.push_str(&value);
+                rest = &rest[rest.find('}').unwrap() + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Single-quotes a value for the shell, so spaces and metacharacters are taken literally.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
 
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
+/// Lists the entries of `dir`. Reading the directory is serial, but the
+/// returned paths can then be checked in parallel.
+fn read_dir_paths(dir: &str) -> Result<Vec<PathBuf>, std::io::Error> {
+    fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect()
+}
 
+fn get_basenames(dir: &str) -> Result<Vec<String>, std::io::Error> {
+    let basenames = read_dir_paths(dir)?
+        .into_par_iter()
         // Check if the entry is a file with .jpg extension
-        if path.is_file() {
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
             if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
                 if ext.eq_ignore_ascii_case("jpg") {
-                    if let Some(filename) = path.file_stem().and_then(|f| f.to_str()) {
-                        basenames.push(filename.to_string());
-                    }
+                    path.file_stem()
+                        .and_then(|f| f.to_str())
+                        .map(|filename| filename.to_string())
                 } else {
-                    basenames.push(path.file_name().unwrap().to_str().unwrap().to_string());
+                    Some(path.file_name().unwrap().to_str().unwrap().to_string())
                 }
             } else {
-                basenames.push(path.file_name().unwrap().to_str().unwrap().to_string());
+                Some(path.file_name().unwrap().to_str().unwrap().to_string())
             }
-        }
-    }
+        })
+        .collect();
 
     Ok(basenames)
 }
 
 fn get_filenames(dir: &str) -> Result<Vec<String>, std::io::Error> {
-    let mut filenames = Vec::new();
-
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
+    let filenames = read_dir_paths(dir)?
+        .into_par_iter()
         // Check if the entry is a file with .jpg extension
-        if path.is_file() {
-            if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                if ext.eq_ignore_ascii_case("jpg") {
-                    if let Some(filename) = path.file_name().and_then(|f| f.to_str()) {
-                        filenames.push(filename.to_string());
-                    }
-                }
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let ext = path.extension().and_then(|e| e.to_str())?;
+            if ext.eq_ignore_ascii_case("jpg") {
+                path.file_name()
+                    .and_then(|f| f.to_str())
+                    .map(|filename| filename.to_string())
+            } else {
+                None
             }
-        }
-    }
+        })
+        .collect();
 
     Ok(filenames)
 }
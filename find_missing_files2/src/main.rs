@@ -1,26 +1,69 @@
+use clap::Parser;
 use std::collections::{HashMap, HashSet};
-use std::env;
 use std::fs;
 use std::path::Path;
 
-fn main() {
-    // Get command-line arguments
-    let args: Vec<String> = env::args().collect();
+/// Finds base names missing some of their indexed derivative files.
+/// By default, files are named `<base><postfix><index>`; with `--grid`, they
+/// are row/column crops named `<base>_r<row>_c<col>`. With `--contiguous`, any
+/// gap below a base name's highest index is reported, along with indices at or
+/// beyond the expected count.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+#[command(
+    override_usage = "find_missing_files2 <DIRECTORY> <POSTFIX> <EXPECTED_COUNT>\n       find_missing_files2 <DIRECTORY> <POSTFIX> [EXPECTED_COUNT] --contiguous\n       find_missing_files2 <DIRECTORY> --grid <ROWSxCOLS>"
+)]
+struct Args {
+    /// Directory to scan
+    directory: String,
+
+    /// Separator between the base name and the index
+    #[arg(required_unless_present = "grid", conflicts_with = "grid")]
+    postfix: Option<String>,
 
-    if args.len() != 4 {
-        eprintln!("Usage: {} <directory> <postfix> <expected_count>", args[0]);
-        std::process::exit(1);
+    /// Number of indices expected per base name (0 to count - 1)
+    #[arg(
+        required_unless_present_any = ["grid", "contiguous"],
+        conflicts_with = "grid",
+        value_parser = parse_expected_count
+    )]
+    expected_count: Option<usize>,
+
+    /// Expect a ROWSxCOLS grid of `<base>_r<row>_c<col>` crops per base name
+    #[arg(long, value_name = "ROWSxCOLS", value_parser = parse_grid)]
+    grid: Option<(usize, usize)>,
+
+    /// Report gaps up to each base name's highest index, and indices at or beyond
+    /// EXPECTED_COUNT when it is given
+    #[arg(long, conflicts_with = "grid")]
+    contiguous: bool,
+}
+
+fn parse_expected_count(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err("expected count must be a positive integer".to_string()),
     }
+}
 
-    let dir = &args[1];
-    let postfix = &args[2];
-    let expected_count: usize = match args[3].parse() {
-        Ok(n) if n > 0 => n,
-        _ => {
-            eprintln!("Error: Expected count must be a positive integer.");
-            std::process::exit(1);
-        }
-    };
+/// Parses a grid size such as "3x4" into (rows, columns).
+fn parse_grid(s: &str) -> Result<(usize, usize), String> {
+    let (rows, cols) = s
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("invalid grid '{}': expected ROWSxCOLS", s))?;
+    match (rows.trim().parse::<usize>(), cols.trim().parse::<usize>()) {
+        (Ok(rows), Ok(cols)) if rows > 0 && cols > 0 => Ok((rows, cols)),
+        _ => Err(format!(
+            "invalid grid '{}': rows and columns must be positive integers",
+            s
+        )),
+    }
+}
+
+fn main() {
+    // Get command-line arguments
+    let args = Args::parse();
+    let dir = &args.directory;
 
     // Collect filenames from the directory
     let filenames = match get_filenames(dir) {
@@ -31,23 +74,46 @@ fn main() {
         }
     };
 
-    // Map base names to available indices
-    let mut base_name_map: HashMap<String, HashSet<usize>> = HashMap::new();
+    if let Some((rows, cols)) = args.grid {
+        report_missing_grid_cells(dir, &filenames, rows, cols);
+        return;
+    }
+
+    // clap requires the postfix whenever --grid is absent
+    let Some(postfix) = &args.postfix else {
+        unreachable!("clap requires a postfix without --grid");
+    };
+
+    // Map base names to available indices and the files holding them
+    let mut base_name_map: HashMap<String, HashMap<usize, String>> = HashMap::new();
 
     for filename in filenames {
         let path = Path::new(&filename);
         if let Some((base_name, index)) = extract_base_name_and_index(path, postfix) {
-            base_name_map.entry(base_name).or_default().insert(index);
+            base_name_map
+                .entry(base_name)
+                .or_default()
+                .insert(index, filename);
         }
     }
 
+    if args.contiguous {
+        report_index_gaps(dir, &base_name_map, postfix, args.expected_count);
+        return;
+    }
+
+    // clap requires the expected count unless --grid or --contiguous is given
+    let Some(expected_count) = args.expected_count else {
+        unreachable!("clap requires an expected count without --contiguous");
+    };
+
     // Check for missing indices for each base name
     let mut bases_with_missing_files = Vec::new();
 
     for (base_name, indices) in &base_name_map {
         let mut missing_indices = Vec::new();
         for i in 0..expected_count {
-            if !indices.contains(&i) {
+            if !indices.contains_key(&i) {
                 missing_indices.push(i);
             }
         }
@@ -76,6 +142,150 @@ fn main() {
     }
 }
 
+/// Reports, per base name, gaps in its indices up to the highest one found (or
+/// up to `expected_count - 1` if that is higher), with runs of missing indices
+/// shown as ranges, and files whose index is at or beyond `expected_count`.
+fn report_index_gaps(
+    dir: &str,
+    base_name_map: &HashMap<String, HashMap<usize, String>>,
+    postfix: &str,
+    expected_count: Option<usize>,
+) {
+    let mut base_names: Vec<&String> = base_name_map.keys().collect();
+    base_names.sort();
+
+    let mut irregular_bases = 0;
+    for base_name in base_names {
+        let files = &base_name_map[base_name];
+        let gaps = index_gaps(files.keys().copied(), expected_count);
+        let mut extras: Vec<(usize, &String)> = match expected_count {
+            Some(count) => files
+                .iter()
+                .filter(|(&index, _)| index >= count)
+                .map(|(&index, filename)| (index, filename))
+                .collect(),
+            None => Vec::new(),
+        };
+        extras.sort();
+        if gaps.is_empty() && extras.is_empty() {
+            continue;
+        }
+
+        if irregular_bases == 0 {
+            println!(
+                "Base names with gaps or extra indices in directory '{}':",
+                dir
+            );
+        }
+        irregular_bases += 1;
+        println!("Base name: {}", base_name);
+        if !gaps.is_empty() {
+            println!("Missing files:");
+            for (first, last) in gaps {
+                if first == last {
+                    println!("  {}{}{}.jpg", base_name, postfix, first);
+                } else {
+                    println!(
+                        "  {base}{postfix}{first}.jpg .. {base}{postfix}{last}.jpg ({} files)",
+                        last - first + 1,
+                        base = base_name,
+                    );
+                }
+            }
+        }
+        if let (false, Some(count)) = (extras.is_empty(), expected_count) {
+            println!("Extra files (index {} or above):", count);
+            for (_, filename) in extras {
+                println!("  {}", filename);
+            }
+        }
+        println!();
+    }
+
+    if irregular_bases == 0 {
+        println!(
+            "All base names have contiguous indices with postfix '{}' in '{}'.",
+            postfix, dir
+        );
+    }
+}
+
+/// Returns the missing indices below the highest of `indices` and, when given,
+/// below `expected_count`, as inclusive `(first, last)` ranges so that a stray
+/// huge index yields one range rather than billions of lines.
+fn index_gaps(
+    indices: impl Iterator<Item = usize>,
+    expected_count: Option<usize>,
+) -> Vec<(usize, usize)> {
+    let mut indices: Vec<usize> = indices.collect();
+    indices.sort_unstable();
+
+    let mut gaps = Vec::new();
+    // `None` once an index of usize::MAX leaves nothing above it to check
+    let mut next = Some(0);
+    for index in indices {
+        if let Some(next) = next.filter(|&next| index > next) {
+            gaps.push((next, index - 1));
+        }
+        next = index.checked_add(1);
+    }
+    if let (Some(next), Some(count)) = (next, expected_count) {
+        if next < count {
+            gaps.push((next, count - 1));
+        }
+    }
+    gaps
+}
+
+/// Reports base names missing some `(row, col)` crops of a `rows` x `cols` grid.
+fn report_missing_grid_cells(dir: &str, filenames: &[String], rows: usize, cols: usize) {
+    // Map base names to available cells
+    let mut base_name_map: HashMap<String, HashSet<(usize, usize)>> = HashMap::new();
+
+    for filename in filenames {
+        let path = Path::new(filename);
+        if let Some((base_name, cell)) = extract_base_name_and_cell(path) {
+            base_name_map.entry(base_name).or_default().insert(cell);
+        }
+    }
+
+    // Check for missing cells for each base name
+    let mut bases_with_missing_files = Vec::new();
+
+    for (base_name, cells) in &base_name_map {
+        let mut missing_cells = Vec::new();
+        for row in 0..rows {
+            for col in 0..cols {
+                if !cells.contains(&(row, col)) {
+                    missing_cells.push((row, col));
+                }
+            }
+        }
+        if !missing_cells.is_empty() {
+            bases_with_missing_files.push((base_name.clone(), missing_cells));
+        }
+    }
+
+    // Display the result
+    if bases_with_missing_files.is_empty() {
+        println!(
+            "All base names have all {}x{} grid crops in '{}'.",
+            rows, cols, dir
+        );
+    } else {
+        println!("Base names missing grid crops in directory '{}':", dir);
+        for (base_name, missing_cells) in bases_with_missing_files {
+            println!("Base name: {}", base_name);
+            println!("Missing files:");
+            for (row, col) in missing_cells {
+                let missing_file = format!("{}_r{}_c{}.jpg", base_name, row, col);
+                println!("  {} (row {}, col {})", missing_file, row, col);
+            }
+            println!();
+        }
+    }
+}
+
 fn get_filenames(dir: &str) -> Result<Vec<String>, std::io::Error> {
     let mut filenames = Vec::new();
 
@@ -105,3 +315,45 @@ fn extract_base_name_and_index(path: &Path, postfix: &str) -> Option<(String, us
     }
     None
 }
+
+/// Splits a `<base>_r<row>_c<col>` file stem into its base name and cell.
+fn extract_base_name_and_cell(path: &Path) -> Option<(String, (usize, usize))> {
+    let filename = path.file_stem()?.to_str()?;
+    let (rest, col_str) = filename.rsplit_once("_c")?;
+    let (base_name, row_str) = rest.rsplit_once("_r")?;
+    let row = row_str.parse::<usize>().ok()?;
+    let col = col_str.parse::<usize>().ok()?;
+    Some((base_name.to_string(), (row, col)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_gaps_reports_missing_indices_below_the_expected_count() {
+        assert_eq!(index_gaps([0, 1, 2, 5].into_iter(), Some(4)), vec![(3, 4)]);
+    }
+
+    #[test]
+    fn index_gaps_extends_to_the_expected_count() {
+        assert_eq!(
+            index_gaps([0, 2].into_iter(), Some(5)),
+            vec![(1, 1), (3, 4)]
+        );
+        assert_eq!(index_gaps([1, 2].into_iter(), None), vec![(0, 0)]);
+        assert!(index_gaps([0, 1, 2].into_iter(), None).is_empty());
+    }
+
+    #[test]
+    fn index_gaps_compresses_huge_indices_without_overflow() {
+        assert_eq!(
+            index_gaps([0, 3_000_000_000].into_iter(), None),
+            vec![(1, 2_999_999_999)]
+        );
+        assert_eq!(
+            index_gaps([0, usize::MAX].into_iter(), Some(4)),
+            vec![(1, usize::MAX - 1)]
+        );
+    }
+}
@@ -2,8 +2,10 @@ use std::error::Error;
 use std::fs::File;
 use std::io::{self, BufRead};
 
-use clap::Parser;
+use chrono::Local;
+use clap::{Parser, ValueEnum};
 use csv::Writer;
+use regex::Regex;
 use rust_xlsxwriter::Workbook;
 
 #[derive(Parser, Debug)]
@@ -21,9 +23,67 @@ struct Args {
     #[arg(long, value_name = "INPUT_FILE")]
     input: String,
 
-    /// Output file path
-    #[arg(long, value_name = "OUTPUT_FILE")]
-    output: String,
+    /// Output file path (.xlsx or .csv), or "-" to write CSV to stdout
+    #[arg(long, value_name = "OUTPUT_FILE", required_unless_present = "validate")]
+    output: Option<String>,
+
+    /// Write percentages such as "45%" as fractions (0.45) instead of plain numbers (45)
+    #[arg(long)]
+    percent_as_fraction: bool,
+
+    /// Input file layout
+    #[arg(long, value_enum, default_value_t = InputFormat::Legacy)]
+    format: InputFormat,
+
+    /// Only check that every result has one value per k column, exiting non-zero
+    /// if any does not; no output is written
+    #[arg(long)]
+    validate: bool,
+
+    /// Prepend `source_file` and `run_time` columns naming the input file and
+    /// when this run happened, to tell rows from different runs apart
+    #[arg(long)]
+    add_source_column: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum InputFormat {
+    /// A "<name> dataset" line followed by "<result>: [values]" lines
+    Legacy,
+    /// One "<dataset> <result>: [values]" line per result
+    Inline,
+}
+
+/// Output columns: four identifying columns followed by one column per k.
+const HEADERS: [&str; 16] = [
+    "benchmark",
+    "module",
+    "dataset",
+    "result",
+    "3",
+    "5",
+    "10",
+    "20",
+    "30",
+    "40",
+    "50",
+    "60",
+    "70",
+    "80",
+    "90",
+    "100",
+];
+
+/// Number of identifying columns before the values in `HEADERS`.
+const KEY_COLUMNS: usize = 4;
+
+/// Columns written before `HEADERS` with `--add-source-column`.
+const SOURCE_HEADERS: [&str; 2] = ["source_file", "run_time"];
+
+/// Where and when the rows of this run came from.
+struct Provenance {
+    source_file: String,
+    run_time: String,
 }
 
 struct DataEntry {
@@ -41,11 +101,32 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Parse the input file
     let data_entries = parse_input_file(&args)?;
 
-    // Determine output format based on file extension
-    if args.output.ends_with(".xlsx") {
-        write_excel(&data_entries, &args.output)?;
-    } else if args.output.ends_with(".csv") {
-        write_csv(&data_entries, &args.output)?;
+    if args.validate {
+        if !validate_entries(&data_entries) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // clap requires --output unless --validate is given
+    let output = args.output.as_deref().unwrap_or_default();
+
+    // One timestamp for the whole run, so its rows share it
+    let provenance = args.add_source_column.then(|| Provenance {
+        source_file: args.input.clone(),
+        run_time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    });
+    let provenance = provenance.as_ref();
+
+    // Determine output format based on file extension; "-" is CSV on stdout
+    if output == "-" {
+        let wtr = Writer::from_writer(io::stdout());
+        write_csv(&data_entries, wtr, args.percent_as_fraction, provenance)?;
+    } else if output.ends_with(".xlsx") {
+        write_excel(&data_entries, output, args.percent_as_fraction, provenance)?;
+    } else if output.ends_with(".csv") {
+        let wtr = Writer::from_path(output)?;
+        write_csv(&data_entries, wtr, args.percent_as_fraction, provenance)?;
     } else {
         eprintln!("Unsupported output file format. Please use .xlsx or .csv extension.");
         std::process::exit(1);
@@ -54,13 +135,54 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn parse_input_file(args: &Args) -> Result<Vec<DataEntry>, Box<dyn Error>> {
-    let mut data_entries = Vec::new();
-    let mut current_dataset = String::new();
+/// Reports every result whose value count differs from the number of k
+/// columns. Returns whether all results are well formed.
+fn validate_entries(data_entries: &[DataEntry]) -> bool {
+    let expected = HEADERS.len() - KEY_COLUMNS;
+    let mut invalid = 0;
+    for entry in data_entries {
+        if entry.values.len() != expected {
+            eprintln!(
+                "Dataset '{}', result '{}': {} values, expected {}",
+                entry.dataset,
+                entry.result,
+                entry.values.len(),
+                expected
+            );
+            invalid += 1;
+        }
+    }
+
+    if invalid == 0 {
+        println!(
+            "All {} results have {} values.",
+            data_entries.len(),
+            expected
+        );
+    } else {
+        eprintln!(
+            "{} of {} results have the wrong number of values.",
+            invalid,
+            data_entries.len()
+        );
+    }
+    invalid == 0
+}
 
+fn parse_input_file(args: &Args) -> Result<Vec<DataEntry>, Box<dyn Error>> {
     let file = File::open(&args.input)?;
     let reader = io::BufReader::new(file);
 
+    match args.format {
+        InputFormat::Legacy => parse_legacy(args, reader),
+        InputFormat::Inline => parse_inline(args, reader),
+    }
+}
+
+fn parse_legacy(args: &Args, reader: impl BufRead) -> Result<Vec<DataEntry>, Box<dyn Error>> {
+    let mut data_entries = Vec::new();
+    let mut current_dataset = String::new();
+
     for line_result in reader.lines() {
         let line = line_result?;
         let line = line.trim();
@@ -74,15 +196,7 @@ fn parse_input_file(args: &Args) -> Result<Vec<DataEntry>, Box<dyn Error>> {
             let result_name = parts[0].trim();
             let values_str = parts[1].trim();
 
-            // Extract values inside square brackets
-            if values_str.starts_with('[') && values_str.ends_with(']') {
-                let values_content = &values_str[1..values_str.len() - 1];
-                // Split values by comma
-                let values: Vec<String> = values_content
-                    .split(',')
-                    .map(|s| s.trim().to_string())
-                    .collect();
-
+            if let Some(values) = parse_values(values_str) {
                 // Create a DataEntry and add to the vector
                 data_entries.push(DataEntry {
                     benchmark: args.benchmark.clone(),
@@ -102,35 +216,90 @@ fn parse_input_file(args: &Args) -> Result<Vec<DataEntry>, Box<dyn Error>> {
     Ok(data_entries)
 }
 
-fn write_excel(data_entries: &[DataEntry], output_file: &str) -> Result<(), Box<dyn Error>> {
+/// Parses lines that carry the dataset alongside the result,
+/// e.g. `cifar10 recall@k: [0.91, 0.95]`.
+fn parse_inline(args: &Args, reader: impl BufRead) -> Result<Vec<DataEntry>, Box<dyn Error>> {
+    let line_re = Regex::new(r"^(\S+)\s+([^:]+?)\s*:\s*(\[.*\])$")?;
+    let mut data_entries = Vec::new();
+
+    for line_result in reader.lines() {
+        let line = line_result?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parsed = line_re.captures(line).and_then(|caps| {
+            Some((
+                caps[1].to_string(),
+                caps[2].to_string(),
+                parse_values(&caps[3])?,
+            ))
+        });
+        match parsed {
+            Some((dataset, result, values)) => data_entries.push(DataEntry {
+                benchmark: args.benchmark.clone(),
+                module: args.module.clone(),
+                dataset,
+                result,
+                values,
+            }),
+            None => eprintln!("Invalid inline format: {}", line),
+        }
+    }
+
+    Ok(data_entries)
+}
+
+/// Extracts the comma-separated values inside square brackets.
+fn parse_values(values_str: &str) -> Option<Vec<String>> {
+    let values_content = values_str.strip_prefix('[')?.strip_suffix(']')?;
+    Some(
+        values_content
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .collect(),
+    )
+}
+
+/// Parses a result value as a number, accepting plain and scientific notation
+/// (`1.2e-3`) as well as percentages (`45%`), which become `0.45` when
+/// `percent_as_fraction` is set and `45` otherwise.
+fn parse_value(value: &str, percent_as_fraction: bool) -> Option<f64> {
+    match value.strip_suffix('%') {
+        Some(percent) => {
+            let num = percent.trim().parse::<f64>().ok()?;
+            Some(if percent_as_fraction {
+                num / 100.0
+            } else {
+                num
+            })
+        }
+        None => value.parse::<f64>().ok(),
+    }
+}
+
+fn write_excel(
+    data_entries: &[DataEntry],
+    output_file: &str,
+    percent_as_fraction: bool,
+    provenance: Option<&Provenance>,
+) -> Result<(), Box<dyn Error>> {
     // Create a new workbook
     let mut workbook = Workbook::new();
 
     // Add a worksheet
     let worksheet = workbook.add_worksheet();
 
+    // Source columns, when present, shift everything else to the right
+    let offset = if provenance.is_some() {
+        SOURCE_HEADERS.len()
+    } else {
+        0
+    };
+
     // Write the header row
-    let headers = vec![
-        "benchmark",
-        "module",
-        "dataset",
-        "result",
-        "3",
-        "5",
-        "10",
-        "20",
-        "30",
-        "40",
-        "50",
-        "60",
-        "70",
-        "80",
-        "90",
-        "100",
-    ];
-
-    // Write the headers
-    for (col_num, header) in headers.iter().enumerate() {
+    let headers = SOURCE_HEADERS[..offset].iter().chain(HEADERS.iter());
+    for (col_num, header) in headers.enumerate() {
         worksheet.write(0, col_num as u16, *header)?;
     }
 
@@ -138,22 +307,27 @@ fn write_excel(data_entries: &[DataEntry], output_file: &str) -> Result<(), Box<
     for (row_num, entry) in data_entries.iter().enumerate() {
         let row = (row_num + 1) as u32;
 
-        // Column indices:
+        if let Some(provenance) = provenance {
+            worksheet.write_string(row, 0, &provenance.source_file)?;
+            worksheet.write_string(row, 1, &provenance.run_time)?;
+        }
+
+        // Column indices, after any source columns:
         // Column 0: benchmark
         // Column 1: module
         // Column 2: dataset
         // Column 3: result
         // Columns 4 onward: values
-
-        worksheet.write_string(row, 0, &entry.benchmark)?;
-        worksheet.write_string(row, 1, &entry.module)?;
-        worksheet.write_string(row, 2, &entry.dataset)?;
-        worksheet.write_string(row, 3, &entry.result)?;
+        let key_col = offset as u16;
+        worksheet.write_string(row, key_col, &entry.benchmark)?;
+        worksheet.write_string(row, key_col + 1, &entry.module)?;
+        worksheet.write_string(row, key_col + 2, &entry.dataset)?;
+        worksheet.write_string(row, key_col + 3, &entry.result)?;
 
         // Write the values
         for (i, value) in entry.values.iter().enumerate() {
-            let col = (i + 4) as u16;
-            if let Ok(num) = value.parse::<f64>() {
+            let col = (offset + KEY_COLUMNS + i) as u16;
+            if let Some(num) = parse_value(value, percent_as_fraction) {
                 worksheet.write_number(row, col, num)?;
             } else {
                 worksheet.write_string(row, col, value)?;
@@ -167,42 +341,44 @@ fn write_excel(data_entries: &[DataEntry], output_file: &str) -> Result<(), Box<
     Ok(())
 }
 
-fn write_csv(data_entries: &[DataEntry], output_file: &str) -> Result<(), Box<dyn Error>> {
-    let mut wtr = Writer::from_path(output_file)?;
-
+fn write_csv<W: io::Write>(
+    data_entries: &[DataEntry],
+    mut wtr: Writer<W>,
+    percent_as_fraction: bool,
+    provenance: Option<&Provenance>,
+) -> Result<(), Box<dyn Error>> {
     // Write the header row
-    let headers = vec![
-        "benchmark",
-        "module",
-        "dataset",
-        "result",
-        "3",
-        "5",
-        "10",
-        "20",
-        "30",
-        "40",
-        "50",
-        "60",
-        "70",
-        "80",
-        "90",
-        "100",
-    ];
-
+    let mut headers = Vec::new();
+    if provenance.is_some() {
+        headers.extend(SOURCE_HEADERS);
+    }
+    headers.extend(HEADERS);
     wtr.write_record(&headers)?;
 
     // Write the data entries
     for entry in data_entries {
-        let mut row = vec![
+        let mut row = Vec::new();
+        if let Some(provenance) = provenance {
+            row.push(provenance.source_file.clone());
+            row.push(provenance.run_time.clone());
+        }
+        row.extend([
             entry.benchmark.clone(),
             entry.module.clone(),
             entry.dataset.clone(),
             entry.result.clone(),
-        ];
-
-        // Append the values
-        row.extend(entry.values.clone());
+        ]);
+
+        // Append the values, writing percentages as plain numbers
+        row.extend(entry.values.iter().map(|value| {
+            match (
+                value.ends_with('%'),
+                parse_value(value, percent_as_fraction),
+            ) {
+                (true, Some(num)) => num.to_string(),
+                _ => value.clone(),
+            }
+        }));
 
         wtr.write_record(&row)?;
     }
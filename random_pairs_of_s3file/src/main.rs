@@ -1,16 +1,17 @@
-use clap::Parser;
-use rand::seq::SliceRandom;
+use clap::{Parser, ValueEnum};
+use rand::seq::{index, SliceRandom};
+use rand::Rng;
 use serde::Serialize;
 use std::collections::HashSet;
 use std::error::Error;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
 
 // AWS SDK for Rust (1.x)
 use aws_config::{load_defaults, BehaviorVersion};
 use aws_sdk_s3::error::SdkError;
-use aws_sdk_s3::types::Object;
 use aws_sdk_s3::Client;
+use cli_common::s3::list_all_keys;
 
 /// Command-line arguments (all required, no defaults)
 #[derive(Parser, Debug)]
@@ -28,13 +29,44 @@ struct Args {
     #[arg(long, required = true)]
     directory: String,
 
-    /// URL prefix to form the final URL (e.g. "https://api.example.com/s3/api/v1/resource?url=s3://")
-    #[arg(long, required = true)]
-    url_prefix: String,
+    /// URL prefix to form the final URL (e.g. "https://api.example.com/s3/api/v1/resource?url=s3://");
+    /// not needed with --raw-keys
+    #[arg(long, required_unless_present = "raw_keys")]
+    url_prefix: Option<String>,
 
     /// File containing keys to exclude
     #[arg(long, required = false)]
     exclude_file: Option<String>,
+
+    /// Spread pairs evenly over source keys instead of sampling uniformly from all pairs.
+    /// With fewer pairs than keys, each pair gets a different randomly chosen source.
+    #[arg(long)]
+    balanced: bool,
+
+    /// Output format: a single JSON document, or one JSON object per line
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    format: OutputFormat,
+
+    /// Emit the bare object key (`key`, the default) or an S3 URI (`uri`,
+    /// s3://bucket/key) instead of the URL built from --url-prefix
+    #[arg(long, value_enum, value_name = "FORM", num_args = 0..=1, default_missing_value = "key")]
+    raw_keys: Option<RawKeys>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum RawKeys {
+    /// The object key as listed
+    Key,
+    /// s3://bucket/key
+    Uri,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// {"pairs": [...]} pretty-printed
+    Json,
+    /// JSON Lines: one {"source", "candidate"} object per line
+    Jsonl,
 }
 
 #[derive(Serialize)]
@@ -55,7 +87,6 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let num_pairs = args.num_pairs;
     let bucket_name = &args.bucket;
     let directory_prefix = &args.directory;
-    let url_prefix = &args.url_prefix;
 
     // Read excluded keys from file if provided
     let excluded_keys: HashSet<String> = if let Some(exclude_file_path) = args.exclude_file {
@@ -68,15 +99,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let shared_config = load_defaults(BehaviorVersion::latest()).await;
     let s3_client = Client::new(&shared_config);
 
-    let resp = s3_client
-        .list_objects_v2()
-        .bucket(bucket_name)
-        .prefix(directory_prefix)
-        .send()
-        .await;
-
-    let output = match resp {
-        Ok(o) => o,
+    // List every key under the prefix, following continuation tokens
+    let keys = match list_all_keys(&s3_client, bucket_name, directory_prefix).await {
+        Ok(keys) => keys,
         Err(SdkError::ServiceError(e)) => {
             eprintln!("Service error: {:#?}", e);
             return Ok(());
@@ -87,11 +112,25 @@ async fn main() -> Result<(), Box<dyn Error>> {
         }
     };
 
-    // Extract all object keys
-    let objects: &[Object] = output.contents();
-    let all_keys: Vec<String> = objects
-        .iter()
-        .filter_map(|obj| obj.key().map(str::to_string))
+    // Drop duplicate keys, keeping the first occurrence of each
+    let listed = keys.len();
+    let mut seen: HashSet<String> = HashSet::with_capacity(listed);
+    let keys: Vec<String> = keys
+        .into_iter()
+        .filter(|key| seen.insert(key.clone()))
+        .collect();
+    let duplicates = listed - keys.len();
+    if duplicates > 0 {
+        eprintln!(
+            "Collapsed {} duplicate key(s) from the listing ({} unique of {} listed).",
+            duplicates,
+            keys.len(),
+            listed
+        );
+    }
+
+    let all_keys: Vec<String> = keys
+        .into_iter()
         .filter(|key| !excluded_keys.contains(key))
         .collect();
 
@@ -103,42 +142,28 @@ async fn main() -> Result<(), Box<dyn Error>> {
         return Ok(());
     }
 
-    // Generate all unique pairs (source, candidate) where source != candidate
-    let mut all_pairs = Vec::new();
-    for (i, source) in all_keys.iter().enumerate() {
-        // check if source is empty
-        if source.is_empty() || source.ends_with('/') {
-            continue;
-        }
-        for (j, candidate) in all_keys.iter().enumerate() {
-            // check if candidate is is_empty
-            if candidate.is_empty() || candidate.ends_with('/') {
-                continue;
-            }
-            if i != j {
-                all_pairs.push(Pair {
-                    source: format!("{}{}/{}", url_prefix, bucket_name, source),
-                    candidate: format!("{}{}/{}", url_prefix, bucket_name, candidate),
-                });
-            }
-        }
-    }
-
-    let max_pairs_possible = all_pairs.len();
-    if num_pairs > max_pairs_possible {
-        eprintln!(
-            "Requested {} pairs, but only {} unique pairs can be generated with {} objects.",
-            num_pairs,
-            max_pairs_possible,
-            all_keys.len()
-        );
-    }
+    // Only real objects take part, not empty keys or "directory" placeholders
+    let keys: Vec<&String> = all_keys
+        .iter()
+        .filter(|key| !key.is_empty() && !key.ends_with('/'))
+        .collect();
 
-    // Shuffle and take the requested number of pairs
+    // Select (source, candidate) index pairs where source != candidate
     let mut rng = rand::thread_rng();
-    all_pairs.shuffle(&mut rng);
-
-    let selected_pairs: Vec<Pair> = all_pairs.into_iter().take(num_pairs).collect();
+    let selected_pairs = if args.balanced {
+        balanced_pairs(keys.len(), num_pairs, &mut rng)
+    } else {
+        let max_pairs_possible = keys.len().saturating_mul(keys.len().saturating_sub(1));
+        if num_pairs > max_pairs_possible {
+            eprintln!(
+                "Requested {} pairs, but only {} unique pairs can be generated with {} objects.",
+                num_pairs,
+                max_pairs_possible,
+                all_keys.len()
+            );
+        }
+        uniform_pairs(keys.len(), num_pairs, &mut rng)
+    };
 
     if selected_pairs.len() < num_pairs {
         eprintln!(
@@ -149,11 +174,95 @@ async fn main() -> Result<(), Box<dyn Error>> {
         );
     }
 
-    // Print JSON output
-    let output_json = PairsOutput {
-        pairs: selected_pairs,
+    let locate = |key: &str| match (args.raw_keys, &args.url_prefix) {
+        (Some(RawKeys::Key), _) => key.to_string(),
+        (Some(RawKeys::Uri), _) => format!("s3://{}/{}", bucket_name, key),
+        (None, url_prefix) => format!(
+            "{}{}/{}",
+            url_prefix.as_deref().unwrap_or_default(),
+            bucket_name,
+            key
+        ),
+    };
+    let to_pair = |(i, j): (usize, usize)| Pair {
+        source: locate(keys[i]),
+        candidate: locate(keys[j]),
     };
-    println!("{}", serde_json::to_string_pretty(&output_json)?);
+
+    match args.format {
+        OutputFormat::Json => {
+            // Print JSON output
+            let output_json = PairsOutput {
+                pairs: selected_pairs.into_iter().map(to_pair).collect(),
+            };
+            println!("{}", serde_json::to_string_pretty(&output_json)?);
+        }
+        OutputFormat::Jsonl => {
+            // One object per line; the URL strings are built only as each line is written
+            let mut out = BufWriter::new(io::stdout().lock());
+            for pair in selected_pairs {
+                serde_json::to_writer(&mut out, &to_pair(pair))?;
+                writeln!(out)?;
+            }
+            out.flush()?;
+        }
+    }
 
     Ok(())
 }
+
+/// Samples up to `num_pairs` distinct (source, candidate) index pairs uniformly
+/// from all `n * (n - 1)` ordered pairs without materializing them. Only the
+/// selected indices are held in memory.
+fn uniform_pairs<R: Rng>(n: usize, num_pairs: usize, rng: &mut R) -> Vec<(usize, usize)> {
+    if n < 2 {
+        return Vec::new();
+    }
+    let total = n.saturating_mul(n - 1);
+    index::sample(rng, total, num_pairs.min(total))
+        .into_iter()
+        .map(|k| {
+            // Row k / (n - 1) is the source; skip over it in the candidate column
+            let source = k / (n - 1);
+            let candidate = k % (n - 1);
+            let candidate = if candidate >= source {
+                candidate + 1
+            } else {
+                candidate
+            };
+            (source, candidate)
+        })
+        .collect()
+}
+
+/// Distributes `num_pairs` as evenly as possible over `n` source indices and
+/// picks distinct random candidates (never the source itself) for each one.
+/// Sources receiving the extra pairs are chosen at random; each source gets at
+/// most `n - 1` candidates. Returns `(source, candidate)` index pairs in random order.
+fn balanced_pairs<R: Rng>(n: usize, num_pairs: usize, rng: &mut R) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    if n < 2 {
+        return pairs;
+    }
+
+    let mut sources: Vec<usize> = (0..n).collect();
+    sources.shuffle(rng);
+
+    let base = num_pairs / n;
+    let extra = num_pairs % n;
+    for (position, &source) in sources.iter().enumerate() {
+        let quota = (base + usize::from(position < extra)).min(n - 1);
+        // Sample from the other n - 1 keys by skipping over the source index
+        for candidate in index::sample(rng, n - 1, quota) {
+            let candidate = if candidate >= source {
+                candidate + 1
+            } else {
+                candidate
+            };
+            pairs.push((source, candidate));
+        }
+    }
+
+    pairs.shuffle(rng);
+    pairs
+}